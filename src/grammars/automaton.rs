@@ -0,0 +1,501 @@
+//! LR(0), LALR(1) and canonical LR(1) automata for a CFG, with their
+//! conflicts, and export to Graphviz DOT.
+//!
+//! The CFG is augmented with a rule `S': S` for its start symbol `S`, and
+//! `$end` marks the end of the input. LR(1) states are built from items
+//! carrying a lookahead; LR(0) states from items without; and LALR(1)
+//! states by merging the LR(1) states with the same items bar lookaheads.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::grammars::{Cfg, term_str};
+use crate::grammars::transform::{IdxRules, Sym};
+
+/// Which automaton to build.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LrKind {
+    /// No lookahead: a state reducing must do nothing else
+    Lr0,
+    Lalr1,
+    Lr1,
+}
+
+impl fmt::Display for LrKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LrKind::Lr0 => write!(f, "LR(0)"),
+            LrKind::Lalr1 => write!(f, "LALR(1)"),
+            LrKind::Lr1 => write!(f, "LR(1)"),
+        }
+    }
+}
+
+/// A grammar symbol, by index into the terminals or rules.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum GSym {
+    Term(usize),
+    NonTerm(usize),
+}
+
+/// An item: alternative `aidx` of rule `ridx` with the dot before symbol
+/// `dot`, and (except in LR(0) automata) a lookahead terminal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct LrItem {
+    pub(crate) ridx: usize,
+    pub(crate) aidx: usize,
+    pub(crate) dot: usize,
+    pub(crate) la: Option<usize>,
+}
+
+/// The kind of an LR conflict.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+/// Two actions of a state on the same lookahead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LrConflict {
+    pub state: usize,
+    pub kind: ConflictKind,
+    /// The terminal, or `$end`
+    pub lookahead: String,
+    /// The alternatives reduced, as (rule, alternative index)
+    pub reductions: Vec<(String, usize)>,
+}
+
+impl fmt::Display for LrConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::ShiftReduce => "shift/reduce",
+            ConflictKind::ReduceReduce => "reduce/reduce",
+        };
+        write!(f, "state {}: {} conflict on {}", self.state, kind, self.lookahead)
+    }
+}
+
+/// The augmented grammar, with symbols as indices.
+pub(crate) struct LrGrammar {
+    /// Rule names, the augmented start rule last
+    pub(crate) lhss: Vec<String>,
+    pub(crate) alts: Vec<Vec<Vec<GSym>>>,
+    /// Terminal names, `$end` last
+    pub(crate) terms: Vec<String>,
+    /// The terminals which are named tokens
+    pub(crate) named: HashSet<String>,
+    nullable: Vec<bool>,
+    first: Vec<HashSet<usize>>,
+}
+
+impl LrGrammar {
+    pub(crate) fn new(cfg: &Cfg) -> Self {
+        let rules = IdxRules::new(cfg);
+        let mut terms = Vec::<String>::new();
+        let mut alts: Vec<Vec<Vec<GSym>>> = rules.alts
+            .iter()
+            .map(|alts| alts
+                .iter()
+                .map(|alt| alt
+                    .iter()
+                    .filter_map(|sym| match sym {
+                        Sym::Term(tok) => {
+                            let tidx = terms.iter().position(|t| t == tok).unwrap_or_else(|| {
+                                terms.push(tok.to_string());
+                                terms.len() - 1
+                            });
+                            Some(GSym::Term(tidx))
+                        }
+                        Sym::NonTerm(ridx) => ridx.map(GSym::NonTerm),
+                    })
+                    .collect())
+                .collect())
+            .collect();
+        let mut lhss = rules.lhss;
+        let start = lhss.first().cloned().unwrap_or_default();
+        lhss.push(format!("{}'", start));
+        alts.push(if alts.is_empty() { vec![] } else { vec![vec![GSym::NonTerm(0)]] });
+        terms.push("$end".to_string());
+
+        let mut grm = Self {
+            nullable: vec![false; lhss.len()],
+            first: vec![HashSet::new(); lhss.len()],
+            lhss,
+            alts,
+            terms,
+            named: rules.named,
+        };
+        grm.compute_first();
+        grm
+    }
+
+    pub(crate) fn start_ridx(&self) -> usize {
+        self.lhss.len() - 1
+    }
+
+    pub(crate) fn end_tidx(&self) -> usize {
+        self.terms.len() - 1
+    }
+
+    fn compute_first(&mut self) {
+        loop {
+            let mut changed = false;
+            for ridx in 0..self.lhss.len() {
+                for aidx in 0..self.alts[ridx].len() {
+                    let (first, nullable) = self.first_of(&self.alts[ridx][aidx]);
+                    let old_len = self.first[ridx].len();
+                    self.first[ridx].extend(first);
+                    changed |= self.first[ridx].len() != old_len;
+                    if nullable && !self.nullable[ridx] {
+                        self.nullable[ridx] = true;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return;
+            }
+        }
+    }
+
+    /// The terminals starting `syms`, and whether `syms` is nullable.
+    pub(crate) fn first_of(&self, syms: &[GSym]) -> (HashSet<usize>, bool) {
+        let mut first = HashSet::<usize>::new();
+        for sym in syms {
+            match sym {
+                GSym::Term(tidx) => {
+                    first.insert(*tidx);
+                    return (first, false);
+                }
+                GSym::NonTerm(ridx) => {
+                    first.extend(self.first[*ridx].iter().cloned());
+                    if !self.nullable[*ridx] {
+                        return (first, false);
+                    }
+                }
+            }
+        }
+        (first, true)
+    }
+
+    pub(crate) fn sym_name(&self, sym: GSym) -> String {
+        match sym {
+            GSym::Term(tidx) if tidx == self.end_tidx() => self.terms[tidx].to_string(),
+            GSym::Term(tidx) => term_str(&self.terms[tidx], self.named.contains(&self.terms[tidx])),
+            GSym::NonTerm(ridx) => self.lhss[ridx].to_string(),
+        }
+    }
+
+    /// An item without its lookahead: `E: E . '+' T`.
+    pub(crate) fn item_str(&self, ridx: usize, aidx: usize, dot: usize) -> String {
+        let syms: Vec<String> = self.alts[ridx][aidx].iter().map(|sym| self.sym_name(*sym)).collect();
+        format!("{}: {} . {}", self.lhss[ridx], syms[..dot].join(" "), syms[dot..].join(" "))
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub(crate) fn next_sym(&self, item: &LrItem) -> Option<GSym> {
+        self.alts[item.ridx][item.aidx].get(item.dot).cloned()
+    }
+
+    fn closure(&self, kernel: BTreeSet<LrItem>, with_la: bool) -> BTreeSet<LrItem> {
+        let mut items = kernel;
+        let mut todo: Vec<LrItem> = items.iter().cloned().collect();
+        while let Some(item) = todo.pop() {
+            let nt = match self.next_sym(&item) {
+                Some(GSym::NonTerm(nt)) => nt,
+                _ => continue,
+            };
+            let las: Vec<Option<usize>> = if with_la {
+                let rest = &self.alts[item.ridx][item.aidx][item.dot + 1..];
+                let (mut first, nullable) = self.first_of(rest);
+                if nullable {
+                    first.extend(item.la);
+                }
+                first.into_iter().map(Some).collect()
+            } else {
+                vec![None]
+            };
+            for aidx in 0..self.alts[nt].len() {
+                for la in &las {
+                    let new = LrItem { ridx: nt, aidx, dot: 0, la: *la };
+                    if items.insert(new) {
+                        todo.push(new);
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    /// The items (and lookahead) of the completed items in `items`, grouped
+    /// by alternative. In LR(0) automata, a reduction applies on every
+    /// lookahead; the augmented start rule is only ever "reduced" (that is,
+    /// the input accepted) on `$end`.
+    pub(crate) fn reductions(&self, items: &BTreeSet<LrItem>) -> Vec<((usize, usize), BTreeSet<usize>)> {
+        let mut reductions = Vec::<((usize, usize), BTreeSet<usize>)>::new();
+        for item in items.iter().filter(|item| self.next_sym(item).is_none()) {
+            let las: BTreeSet<usize> = match item.la {
+                _ if item.ridx == self.start_ridx() => std::iter::once(self.end_tidx()).collect(),
+                Some(la) => std::iter::once(la).collect(),
+                None => (0..self.terms.len()).collect(),
+            };
+            match reductions.iter_mut().find(|(alt, _)| *alt == (item.ridx, item.aidx)) {
+                Some((_, alt_las)) => alt_las.extend(las),
+                None => reductions.push(((item.ridx, item.aidx), las)),
+            }
+        }
+        reductions
+    }
+}
+
+/// A state of an LR automaton.
+#[derive(Debug, Clone)]
+pub(crate) struct LrState {
+    pub(crate) items: BTreeSet<LrItem>,
+    pub(crate) gotos: Vec<(GSym, usize)>,
+}
+
+/// An LR automaton for a CFG.
+pub struct LrAutomaton {
+    kind: LrKind,
+    pub(crate) grm: LrGrammar,
+    pub(crate) states: Vec<LrState>,
+}
+
+impl LrAutomaton {
+    fn new(cfg: &Cfg, kind: LrKind) -> Self {
+        let grm = LrGrammar::new(cfg);
+        let states = match kind {
+            LrKind::Lr0 => build_states(&grm, false),
+            LrKind::Lr1 => build_states(&grm, true),
+            LrKind::Lalr1 => merge_cores(build_states(&grm, true)),
+        };
+
+        Self { kind, grm, states }
+    }
+
+    pub fn kind(&self) -> LrKind {
+        self.kind
+    }
+
+    pub fn states_len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn conflicts(&self) -> Vec<LrConflict> {
+        let mut conflicts = Vec::<LrConflict>::new();
+        for (sidx, state) in self.states.iter().enumerate() {
+            let reductions = self.grm.reductions(&state.items);
+            for tidx in 0..self.grm.terms.len() {
+                let reducing: Vec<(String, usize)> = reductions
+                    .iter()
+                    .filter(|(_, las)| las.contains(&tidx))
+                    .map(|((ridx, aidx), _)| (self.grm.lhss[*ridx].to_string(), *aidx))
+                    .collect();
+                let shifts = state.gotos.iter().any(|(sym, _)| *sym == GSym::Term(tidx));
+                let kind = if shifts && !reducing.is_empty() {
+                    ConflictKind::ShiftReduce
+                } else if reducing.len() > 1 {
+                    ConflictKind::ReduceReduce
+                } else {
+                    continue;
+                };
+                conflicts.push(LrConflict {
+                    state: sidx,
+                    kind,
+                    lookahead: self.grm.sym_name(GSym::Term(tidx)),
+                    reductions: reducing,
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// The items of a state, kernel items first, merging those differing only
+    /// in lookahead:
+    /// `E: E . '+' T ['+', $end]`.
+    fn item_lines(&self, state: &LrState) -> Vec<String> {
+        let mut lines = Vec::<String>::new();
+        let mut las = HashMap::<(usize, usize, usize), Vec<usize>>::new();
+        let mut cores = Vec::<(usize, usize, usize)>::new();
+        for item in &state.items {
+            let core = (item.ridx, item.aidx, item.dot);
+            if !cores.contains(&core) {
+                cores.push(core);
+            }
+            las.entry(core).or_default().extend(item.la);
+        }
+        // kernel items first
+        let start = self.grm.start_ridx();
+        cores.sort_by_key(|(ridx, _, dot)| *dot == 0 && *ridx != start);
+        for (ridx, aidx, dot) in cores {
+            let mut line = self.grm.item_str(ridx, aidx, dot);
+            let item_las = &las[&(ridx, aidx, dot)];
+            if !item_las.is_empty() {
+                let names: Vec<String> = item_las.iter().map(|t| self.grm.sym_name(GSym::Term(*t))).collect();
+                line += &format!(" [{}]", names.join(", "));
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// The automaton as a DOT graph: a box per state listing its items (and
+    /// their lookaheads), with states in conflict filled in red and their
+    /// conflicts listed.
+    pub fn to_dot(&self) -> String {
+        let conflicts = self.conflicts();
+        let mut dot = format!("digraph lr {{\n  label={};\n  rankdir=LR;\n  node [shape=box, fontname=\"monospace\"];\n",
+                              dot_str(&self.kind.to_string()));
+        for (sidx, state) in self.states.iter().enumerate() {
+            let mut label = format!("{}\\l", sidx);
+            for line in self.item_lines(state) {
+                label += &format!("{}\\l", dot_escape(&line));
+            }
+            let state_conflicts: Vec<&LrConflict> = conflicts.iter().filter(|c| c.state == sidx).collect();
+            for conflict in &state_conflicts {
+                label += &format!("{}\\l", dot_escape(&conflict.to_string()));
+            }
+            let style = if state_conflicts.is_empty() { "" } else { ", color=red, style=filled, fillcolor=\"#ffdddd\"" };
+            dot += &format!("  s{} [label=\"{}\"{}];\n", sidx, label, style);
+        }
+        for (sidx, state) in self.states.iter().enumerate() {
+            for (sym, target) in &state.gotos {
+                dot += &format!("  s{} -> s{} [label={}];\n", sidx, target, dot_str(&self.grm.sym_name(*sym)));
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn dot_str(s: &str) -> String {
+    format!("\"{}\"", dot_escape(s))
+}
+
+fn build_states(grm: &LrGrammar, with_la: bool) -> Vec<LrState> {
+    let start = grm.start_ridx();
+    let la = if with_la { Some(grm.end_tidx()) } else { None };
+    let kernel: BTreeSet<LrItem> = (0..grm.alts[start].len())
+        .map(|aidx| LrItem { ridx: start, aidx, dot: 0, la })
+        .collect();
+    let mut states = vec![LrState { items: grm.closure(kernel, with_la), gotos: vec![] }];
+    let mut ids = HashMap::<BTreeSet<LrItem>, usize>::new();
+    ids.insert(states[0].items.clone(), 0);
+    let mut sidx = 0;
+    while sidx < states.len() {
+        let mut syms = Vec::<GSym>::new();
+        for item in &states[sidx].items {
+            if let Some(sym) = grm.next_sym(item) {
+                if !syms.contains(&sym) {
+                    syms.push(sym);
+                }
+            }
+        }
+        for sym in syms {
+            let kernel: BTreeSet<LrItem> = states[sidx].items
+                .iter()
+                .filter(|item| grm.next_sym(item) == Some(sym))
+                .map(|item| LrItem { dot: item.dot + 1, ..*item })
+                .collect();
+            let items = grm.closure(kernel, with_la);
+            let target = match ids.get(&items) {
+                Some(target) => *target,
+                None => {
+                    ids.insert(items.clone(), states.len());
+                    states.push(LrState { items, gotos: vec![] });
+                    states.len() - 1
+                }
+            };
+            states[sidx].gotos.push((sym, target));
+        }
+        sidx += 1;
+    }
+
+    states
+}
+
+/// Merge the states whose items differ only in lookaheads.
+fn merge_cores(states: Vec<LrState>) -> Vec<LrState> {
+    let core = |state: &LrState| -> BTreeSet<(usize, usize, usize)> {
+        state.items.iter().map(|item| (item.ridx, item.aidx, item.dot)).collect()
+    };
+    let mut merged_ids = HashMap::<BTreeSet<(usize, usize, usize)>, usize>::new();
+    let mut merged_of = Vec::<usize>::new();
+    let mut merged = Vec::<LrState>::new();
+    for state in &states {
+        let id = *merged_ids.entry(core(state)).or_insert_with(|| {
+            merged.push(LrState { items: BTreeSet::new(), gotos: vec![] });
+            merged.len() - 1
+        });
+        merged[id].items.extend(state.items.iter().cloned());
+        merged_of.push(id);
+    }
+    for (sidx, state) in states.iter().enumerate() {
+        let id = merged_of[sidx];
+        if merged[id].gotos.is_empty() {
+            merged[id].gotos = state.gotos.iter().map(|(sym, target)| (*sym, merged_of[*target])).collect();
+        }
+    }
+
+    merged
+}
+
+impl Cfg {
+    pub fn lr_automaton(&self, kind: LrKind) -> LrAutomaton {
+        LrAutomaton::new(self, kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lr0_conflict() {
+        // reducing the empty S needs a lookahead
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S | ;\n").unwrap();
+        let lr0 = cfg.lr_automaton(LrKind::Lr0);
+        assert_eq!(lr0.conflicts()[0].kind, ConflictKind::ShiftReduce);
+        assert_eq!(lr0.conflicts()[0].lookahead, "'a'");
+        assert!(cfg.lr_automaton(LrKind::Lalr1).conflicts().is_empty());
+        assert!(cfg.lr_automaton(LrKind::Lr1).conflicts().is_empty());
+    }
+
+    #[test]
+    fn test_lalr_conflict() {
+        // merging the states after 'a' 'c' and 'b' 'c' mixes up the lookaheads
+        let cfg = Cfg::from_yacc("%%\nS: 'a' A 'd' | 'b' B 'd' | 'a' B 'e' | 'b' A 'e';\nA: 'c';\nB: 'c';\n").unwrap();
+        let lr1 = cfg.lr_automaton(LrKind::Lr1);
+        let lalr = cfg.lr_automaton(LrKind::Lalr1);
+        assert!(lr1.conflicts().is_empty());
+        assert_eq!(lalr.states_len() + 1, lr1.states_len());
+        let conflicts = lalr.conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts.iter().all(|c| c.kind == ConflictKind::ReduceReduce));
+        assert_eq!(conflicts[0].reductions, vec![("A".to_string(), 0), ("B".to_string(), 0)]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' 'x' | 'x';\n").unwrap();
+        let dot = cfg.lr_automaton(LrKind::Lr1).to_dot();
+        assert!(dot.starts_with("digraph lr {\n  label=\"LR(1)\";"));
+        assert!(dot.contains("s0 [label=\"0\\lE': . E [$end]\\lE: . E '+' 'x' ['+', $end]\\lE: . 'x' ['+', $end]\\l\"];"),
+                "{}", dot);
+        assert!(dot.contains("s0 -> s1 [label=\"E\"];"));
+        assert!(!dot.contains("fillcolor"));
+
+        let ambiguous = Cfg::from_yacc("%%\nE: E '+' E | 'x';\n").unwrap();
+        let dot = ambiguous.lr_automaton(LrKind::Lalr1).to_dot();
+        assert!(dot.contains("fillcolor=\"#ffdddd\""));
+        assert!(dot.contains("shift/reduce conflict on '+'"));
+    }
+}
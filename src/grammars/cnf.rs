@@ -0,0 +1,642 @@
+//! Conversion to Chomsky Normal Form, where every alternative is either two
+//! non-terminals or a single terminal (plus, for the start rule only, the
+//! empty alternative).
+//!
+//! The conversion applies the usual steps in turn: START, TERM, BIN, DEL and
+//! UNIT. Each alternative of the result remembers which alternative of the
+//! original grammar it was derived from, so that derivations in the CNF
+//! grammar can be translated back.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+use crate::grammars::{Cfg, CfgRule, LexSymbol, NonTermSymbol, RuleAlt};
+use crate::grammars::transform::{FreshNames, nullable, reachable};
+
+/// What a non-terminal introduced by the conversion stands for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CnfNonTerm {
+    /// The new start symbol (START), deriving the original one
+    Start,
+    /// Derives just the given terminal (TERM)
+    Term(String),
+    /// Derives the tail of a longer alternative (BIN)
+    Bin,
+}
+
+/// Where an alternative of the CNF grammar comes from in the original grammar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CnfOrigin {
+    /// Rule of the original alternative
+    pub lhs: String,
+    /// Index of the original alternative within its rule
+    pub alt: usize,
+    /// For each symbol of the CNF alternative, the symbols of the original
+    /// alternative it stands for. Original symbols not covered were nullable
+    /// and dropped by DEL.
+    pub covers: Vec<Range<usize>>,
+    /// Unit alternatives bypassed by UNIT to get from the CNF rule to `lhs`,
+    /// outermost first
+    pub units: Vec<CnfOrigin>,
+}
+
+impl CnfOrigin {
+    fn new(lhs: &str, alt: usize, len: usize) -> Self {
+        Self {
+            lhs: lhs.to_string(),
+            alt,
+            covers: (0..len).map(|i| i..i + 1).collect(),
+            units: vec![],
+        }
+    }
+}
+
+/// A CFG in Chomsky Normal Form, along with the mapping back to the CFG it
+/// was converted from.
+#[derive(Debug, Clone)]
+pub struct CnfCfg {
+    cfg: Cfg,
+    /// Parallel to the rules and alternatives of `cfg`. `None` for
+    /// alternatives with no counterpart in the original CFG: those of the
+    /// `CnfNonTerm::Term` rules, and the new start rule's empty alternative.
+    origins: Vec<Vec<Option<CnfOrigin>>>,
+    fresh: HashMap<String, CnfNonTerm>,
+}
+
+impl CnfCfg {
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    /// The origin of alternative `alt` of rule `lhs`.
+    pub fn origin(&self, lhs: &str, alt: usize) -> Option<&CnfOrigin> {
+        let ridx = self.cfg.rules.iter().position(|rule| rule.lhs == lhs)?;
+        self.origins[ridx].get(alt)?.as_ref()
+    }
+
+    /// What `nt` stands for, if it was introduced by the conversion.
+    pub fn fresh_non_term(&self, nt: &str) -> Option<&CnfNonTerm> {
+        self.fresh.get(nt)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CnfAlt {
+    syms: Vec<LexSymbol>,
+    origin: Option<CnfOrigin>,
+}
+
+impl CnfAlt {
+    fn unit_target(&self) -> Option<&str> {
+        match self.syms.as_slice() {
+            [LexSymbol::NonTerm(nt)] => Some(nt.tok.as_str()),
+            _ => None,
+        }
+    }
+}
+
+struct CnfRule {
+    lhs: String,
+    alts: Vec<CnfAlt>,
+}
+
+impl CnfRule {
+    fn push_alt(&mut self, alt: CnfAlt) {
+        if !self.alts.iter().any(|a| a.syms == alt.syms) {
+            self.alts.push(alt);
+        }
+    }
+}
+
+struct CnfConverter {
+    rules: Vec<CnfRule>,
+    names: FreshNames,
+    fresh: HashMap<String, CnfNonTerm>,
+}
+
+impl CnfConverter {
+    fn new(cfg: &Cfg) -> Self {
+        let rules = cfg.rules
+            .iter()
+            .map(|rule| CnfRule {
+                lhs: rule.lhs.to_string(),
+                alts: rule.rhs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, alt)| CnfAlt {
+                        syms: alt.lex_symbols.clone(),
+                        origin: Some(CnfOrigin::new(&rule.lhs, i, alt.lex_symbols.len())),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            rules,
+            names: FreshNames::new(cfg),
+            fresh: HashMap::new(),
+        }
+    }
+
+    fn start(&self) -> &str {
+        &self.rules[0].lhs
+    }
+
+    fn fresh_rule(&mut self, base: &str, kind: CnfNonTerm) -> String {
+        let nt = self.names.fresh(base);
+        self.fresh.insert(nt.clone(), kind);
+        nt
+    }
+
+    /// START: if the start symbol occurs on a right-hand side, add a new
+    /// start rule deriving it.
+    fn start_step(&mut self) {
+        let start = self.start().to_string();
+        let on_rhs = self.rules
+            .iter()
+            .flat_map(|rule| rule.alts.iter())
+            .flat_map(|alt| alt.syms.iter())
+            .any(|sym| matches!(sym, LexSymbol::NonTerm(nt) if nt.tok == start));
+        if !on_rhs {
+            return;
+        }
+
+        let new_start = self.fresh_rule(&format!("{}_start", start), CnfNonTerm::Start);
+        let alt = CnfAlt {
+            syms: vec![LexSymbol::NonTerm(NonTermSymbol::new(start))],
+            origin: None,
+        };
+        self.rules.insert(0, CnfRule { lhs: new_start, alts: vec![alt] });
+    }
+
+    /// TERM: in alternatives of two or more symbols, replace each terminal
+    /// `a` by a new non-terminal deriving just `a`.
+    fn term_step(&mut self) {
+        let mut term_nts = HashMap::<String, String>::new();
+        let mut new_rules = Vec::<CnfRule>::new();
+        for ridx in 0..self.rules.len() {
+            for aidx in 0..self.rules[ridx].alts.len() {
+                if self.rules[ridx].alts[aidx].syms.len() < 2 {
+                    continue;
+                }
+                for sidx in 0..self.rules[ridx].alts[aidx].syms.len() {
+                    let term = match &self.rules[ridx].alts[aidx].syms[sidx] {
+                        LexSymbol::Term(t) => t.clone(),
+                        LexSymbol::NonTerm(_) => continue,
+                    };
+                    let tok = term.tok.to_string();
+                    let nt = match term_nts.get(&tok) {
+                        Some(nt) => nt.to_string(),
+                        None => {
+                            let nt = self.fresh_rule(&term_non_term(&tok), CnfNonTerm::Term(tok.clone()));
+                            new_rules.push(CnfRule {
+                                lhs: nt.clone(),
+                                alts: vec![CnfAlt {
+                                    syms: vec![LexSymbol::Term(term)],
+                                    origin: None,
+                                }],
+                            });
+                            term_nts.insert(tok, nt.clone());
+                            nt
+                        }
+                    };
+                    self.rules[ridx].alts[aidx].syms[sidx] = LexSymbol::NonTerm(NonTermSymbol::new(nt));
+                }
+            }
+        }
+        self.rules.append(&mut new_rules);
+    }
+
+    /// BIN: split alternatives of more than two symbols into a chain of
+    /// alternatives of two symbols.
+    fn bin_step(&mut self) {
+        let mut new_rules = Vec::<CnfRule>::new();
+        for ridx in 0..self.rules.len() {
+            let lhs = self.rules[ridx].lhs.to_string();
+            for aidx in 0..self.rules[ridx].alts.len() {
+                if self.rules[ridx].alts[aidx].syms.len() <= 2 {
+                    continue;
+                }
+                let alt = self.rules[ridx].alts[aidx].clone();
+                let origin = alt.origin.expect("only the original alternatives are long");
+                let n = alt.syms.len();
+                // the chain X: Y1 X_1; X_1: Y2 X_2; ...; X_(n-2): Y(n-1) Yn
+                let tails: Vec<String> = (1..n - 1)
+                    .map(|_| self.fresh_rule(&format!("{}_bin", lhs), CnfNonTerm::Bin))
+                    .collect();
+                for i in 0..n - 1 {
+                    let (second, second_cover) = if i == n - 2 {
+                        (alt.syms[n - 1].clone(), origin.covers[n - 1].clone())
+                    } else {
+                        (LexSymbol::NonTerm(NonTermSymbol::new(tails[i].to_string())),
+                         origin.covers[i + 1].start..origin.covers[n - 1].end)
+                    };
+                    let link = CnfAlt {
+                        syms: vec![alt.syms[i].clone(), second],
+                        origin: Some(CnfOrigin {
+                            covers: vec![origin.covers[i].clone(), second_cover],
+                            ..origin.clone()
+                        }),
+                    };
+                    if i == 0 {
+                        self.rules[ridx].alts[aidx] = link;
+                    } else {
+                        new_rules.push(CnfRule { lhs: tails[i - 1].to_string(), alts: vec![link] });
+                    }
+                }
+            }
+        }
+        self.rules.append(&mut new_rules);
+    }
+
+    fn nullable(&self) -> HashSet<String> {
+        let rules: Vec<(&str, Vec<&[LexSymbol]>)> = self.rules
+            .iter()
+            .map(|rule| (rule.lhs.as_str(), rule.alts.iter().map(|alt| alt.syms.as_slice()).collect()))
+            .collect();
+        nullable(&rules)
+    }
+
+    /// DEL: remove empty alternatives (other than the start rule's), adding
+    /// for every alternative its variants without nullable non-terminals.
+    fn del_step(&mut self) {
+        let nullable = self.nullable();
+        let start = self.start().to_string();
+        for rule in self.rules.iter_mut() {
+            let alts = std::mem::take(&mut rule.alts);
+            for alt in alts {
+                // the indices of the symbols kept in each variant, adding one
+                // symbol at a time from the end (and, if it is nullable, also
+                // leaving it out). Variants with the same symbols so far end
+                // up as the same alternatives, so only the first is kept.
+                let mut variants: Vec<Vec<usize>> = vec![vec![]];
+                for (i, sym) in alt.syms.iter().enumerate().rev() {
+                    let is_nullable = matches!(sym, LexSymbol::NonTerm(nt) if nullable.contains(&nt.tok));
+                    let mut seen = HashSet::<Vec<&LexSymbol>>::new();
+                    let mut next = Vec::<Vec<usize>>::new();
+                    for kept in variants {
+                        let mut with_sym = kept.clone();
+                        with_sym.insert(0, i);
+                        let options = if is_nullable { vec![with_sym, kept] } else { vec![with_sym] };
+                        for option in options {
+                            if seen.insert(option.iter().map(|&j| &alt.syms[j]).collect()) {
+                                next.push(option);
+                            }
+                        }
+                    }
+                    variants = next;
+                }
+                for kept in variants {
+                    let syms: Vec<LexSymbol> = kept.iter().map(|&i| alt.syms[i].clone()).collect();
+                    if syms.is_empty() && rule.lhs != start {
+                        continue;
+                    }
+                    let origin = alt.origin.as_ref().map(|o| CnfOrigin {
+                        covers: kept.iter().map(|&i| o.covers[i].clone()).collect(),
+                        ..o.clone()
+                    });
+                    rule.push_alt(CnfAlt { syms, origin });
+                }
+            }
+        }
+    }
+
+    /// UNIT: replace alternatives consisting of a single non-terminal with
+    /// the alternatives of that non-terminal.
+    fn unit_step(&mut self) {
+        let alts_of: HashMap<String, Vec<CnfAlt>> = self.rules
+            .iter()
+            .map(|rule| (rule.lhs.to_string(), rule.alts.clone()))
+            .collect();
+        for rule in self.rules.iter_mut() {
+            let mut seen = HashSet::<String>::new();
+            seen.insert(rule.lhs.to_string());
+            // non-terminal reached, and the origins of the unit alternatives taken
+            let mut queue = VecDeque::<(String, Vec<CnfOrigin>)>::new();
+            queue.push_back((rule.lhs.to_string(), vec![]));
+            rule.alts.clear();
+            while let Some((nt, path)) = queue.pop_front() {
+                for alt in alts_of.get(&nt).into_iter().flatten() {
+                    if let Some(target) = alt.unit_target() {
+                        if seen.insert(target.to_string()) {
+                            let mut path = path.clone();
+                            path.extend(alt.origin.iter().cloned());
+                            queue.push_back((target.to_string(), path));
+                        }
+                        continue;
+                    }
+                    let origin = match &alt.origin {
+                        Some(o) => Some(CnfOrigin { units: path.clone(), ..o.clone() }),
+                        // the alternative of a `CnfNonTerm::Term` rule stands
+                        // for the terminal in the unit alternative leading to it
+                        None => path.split_last().map(|(last, rest)| CnfOrigin {
+                            units: rest.to_vec(),
+                            ..last.clone()
+                        }),
+                    };
+                    rule.push_alt(CnfAlt { syms: alt.syms.clone(), origin });
+                }
+            }
+        }
+    }
+
+    /// Remove the rules left without alternatives (and the alternatives
+    /// referring to them), and the rules no longer reachable from the start rule.
+    fn prune(&mut self) {
+        let start = match self.rules.first() {
+            Some(rule) => rule.lhs.to_string(),
+            None => return,
+        };
+        loop {
+            let empty: HashSet<String> = self.rules
+                .iter()
+                .filter(|rule| rule.alts.is_empty())
+                .map(|rule| rule.lhs.to_string())
+                .collect();
+            if empty.is_empty() {
+                break;
+            }
+            self.rules.retain(|rule| !empty.contains(&rule.lhs));
+            for rule in self.rules.iter_mut() {
+                rule.alts.retain(|alt| !alt.syms.iter().any(|sym|
+                    matches!(sym, LexSymbol::NonTerm(nt) if empty.contains(&nt.tok))));
+            }
+        }
+        if self.rules.first().map_or(true, |rule| rule.lhs != start) {
+            // the start rule derives nothing, so neither does the CFG
+            self.rules.clear();
+            self.fresh.clear();
+            return;
+        }
+
+        let reachable = {
+            let rules: Vec<(&str, Vec<&[LexSymbol]>)> = self.rules
+                .iter()
+                .map(|rule| (rule.lhs.as_str(), rule.alts.iter().map(|alt| alt.syms.as_slice()).collect()))
+                .collect();
+            reachable(&rules)
+        };
+        self.rules.retain(|rule| reachable.contains(&rule.lhs));
+        let rules = &self.rules;
+        self.fresh.retain(|nt, _| rules.iter().any(|rule| &rule.lhs == nt));
+    }
+
+    fn convert(mut self) -> CnfCfg {
+        self.start_step();
+        self.term_step();
+        self.bin_step();
+        self.del_step();
+        self.unit_step();
+        self.prune();
+        self.into_cnf_cfg()
+    }
+
+    fn into_cnf_cfg(self) -> CnfCfg {
+        let mut rules = Vec::<CfgRule>::new();
+        let mut origins = Vec::<Vec<Option<CnfOrigin>>>::new();
+        for rule in self.rules {
+            let (alts, alt_origins): (Vec<RuleAlt>, Vec<Option<CnfOrigin>>) = rule.alts
+                .into_iter()
+                .map(|alt| (RuleAlt::new(alt.syms), alt.origin))
+                .unzip();
+            rules.push(CfgRule::new(rule.lhs, alts));
+            origins.push(alt_origins);
+        }
+
+        CnfCfg {
+            cfg: Cfg::new(rules),
+            origins,
+            fresh: self.fresh,
+        }
+    }
+}
+
+/// An equivalent CFG with no cycles and no empty alternatives (other than a
+/// start rule's, if the start symbol is on no right-hand side).
+pub(crate) fn eps_unit_free(cfg: &Cfg) -> Cfg {
+    cfg.remove_epsilons().remove_units()
+}
+
+/// The name of the non-terminal standing for terminal `tok`, with characters
+/// not allowed in a yacc identifier spelt out as hex codes.
+fn term_non_term(tok: &str) -> String {
+    let name: String = tok
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() {
+            c.to_string()
+        } else {
+            format!("x{:x}", c as u32)
+        })
+        .collect();
+    format!("T_{}", name)
+}
+
+impl Cfg {
+    /// Convert to Chomsky Normal Form.
+    pub fn to_cnf(&self) -> CnfCfg {
+        CnfConverter::new(self).convert()
+    }
+
+    /// An equivalent CFG with no empty alternatives, except that if the
+    /// empty sentence is in the language, the start rule keeps one (after a
+    /// new start rule is added if the start symbol is on a right-hand side).
+    pub fn remove_epsilons(&self) -> Cfg {
+        if self.rules.is_empty() {
+            return self.clone();
+        }
+        let mut conv = CnfConverter::new(self);
+        if conv.nullable().contains(conv.start()) {
+            conv.start_step();
+        }
+        conv.del_step();
+        conv.prune();
+        conv.into_cnf_cfg().cfg
+    }
+
+    /// An equivalent CFG with no alternatives consisting of a single
+    /// non-terminal.
+    pub fn remove_units(&self) -> Cfg {
+        if self.rules.is_empty() {
+            return self.clone();
+        }
+        let mut conv = CnfConverter::new(self);
+        conv.unit_step();
+        conv.prune();
+        conv.into_cnf_cfg().cfg
+    }
+
+    /// Are there no empty alternatives, other than the start rule's when the
+    /// start symbol is on no right-hand side?
+    pub fn is_epsilon_free(&self) -> bool {
+        let start = match self.start_rule() {
+            Some(rule) => rule.lhs.as_str(),
+            None => return true,
+        };
+        let start_on_rhs = self.rules
+            .iter()
+            .flat_map(|rule| rule.rhs.iter())
+            .flat_map(|alt| alt.lex_symbols.iter())
+            .any(|sym| matches!(sym, LexSymbol::NonTerm(nt) if nt.tok == start));
+        self.rules.iter().all(|rule| {
+            rule.rhs.iter().all(|alt| !alt.lex_symbols.is_empty() || (rule.lhs == start && !start_on_rhs))
+        })
+    }
+
+    /// Are there no alternatives consisting of a single non-terminal?
+    pub fn is_unit_free(&self) -> bool {
+        self.rules.iter().all(|rule| {
+            rule.rhs.iter().all(|alt| !matches!(alt.lex_symbols.as_slice(), [LexSymbol::NonTerm(_)]))
+        })
+    }
+
+    /// Is every alternative two non-terminals or a terminal (or, for the
+    /// start rule only, empty), with the start symbol on no right-hand side?
+    pub fn is_cnf(&self) -> bool {
+        let start = match self.start_rule() {
+            Some(rule) => rule.lhs.as_str(),
+            None => return true,
+        };
+        self.rules.iter().all(|rule| {
+            rule.rhs.iter().all(|alt| match alt.lex_symbols.as_slice() {
+                [] => rule.lhs == start,
+                [LexSymbol::Term(_)] => true,
+                [LexSymbol::NonTerm(a), LexSymbol::NonTerm(b)] => a.tok != start && b.tok != start,
+                _ => false,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cnf_simple() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' B 'c' | 'd' 'e';\nB: 'b';\n").unwrap();
+        let cnf = cfg.to_cnf();
+        assert!(cnf.cfg().is_cnf());
+        assert_eq!(cnf.cfg().to_string(),
+                   "S: T_a S_bin | T_d T_e\n;\nB: 'b'\n;\nT_a: 'a'\n;\nT_c: 'c'\n;\n\
+                    T_d: 'd'\n;\nT_e: 'e'\n;\nS_bin: B T_c\n;\n");
+        assert_eq!(cnf.fresh_non_term("T_a"), Some(&CnfNonTerm::Term("a".to_string())));
+        assert_eq!(cnf.fresh_non_term("S_bin"), Some(&CnfNonTerm::Bin));
+        assert_eq!(cnf.fresh_non_term("B"), None);
+
+        let origin = cnf.origin("S_bin", 0).unwrap();
+        assert_eq!((origin.lhs.as_str(), origin.alt), ("S", 0));
+        assert_eq!(origin.covers, vec![1..2, 2..3]);
+        assert_eq!(cnf.origin("S", 0).unwrap().covers, vec![0..1, 1..3]);
+        assert_eq!(cnf.origin("T_a", 0), None);
+    }
+
+    #[test]
+    fn test_cnf_start_del_unit() {
+        // S is on a right-hand side, and nullable
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S 'b' | A;\nA: 'c' | ;\n").unwrap();
+        let cnf = cfg.to_cnf();
+        assert!(cnf.cfg().is_cnf());
+        assert_eq!(cnf.cfg().to_string(),
+                   "S_start:  | T_a S_bin | 'c'\n;\nS: T_a S_bin | 'c'\n;\n\
+                    T_a: 'a'\n;\nT_b: 'b'\n;\nS_bin: S T_b | 'b'\n;\n");
+        assert_eq!(cnf.fresh_non_term("S_start"), Some(&CnfNonTerm::Start));
+        assert_eq!(cnf.origin("S_start", 0), None);
+
+        // `S_start: 'c'` is `A: 'c'`, reached through `S: A`
+        let origin = cnf.origin("S_start", 2).unwrap();
+        assert_eq!((origin.lhs.as_str(), origin.alt), ("A", 0));
+        assert_eq!(origin.units.len(), 1);
+        assert_eq!((origin.units[0].lhs.as_str(), origin.units[0].alt), ("S", 1));
+        // `S_bin: 'b'` is the tail of `S: 'a' S 'b'` with S dropped
+        let origin = cnf.origin("S_bin", 1).unwrap();
+        assert_eq!((origin.lhs.as_str(), origin.alt), ("S", 0));
+        assert_eq!(origin.covers, vec![2..3]);
+    }
+
+    #[test]
+    fn test_cnf_term_unit() {
+        // dropping the nullable B leaves `S: T_a`, which UNIT turns back into `S: 'a'`
+        let cfg = Cfg::from_yacc("%%\nS: 'a' B;\nB: 'b' | ;\n").unwrap();
+        let cnf = cfg.to_cnf();
+        assert!(cnf.cfg().is_cnf());
+        assert_eq!(cnf.cfg().to_string(), "S: T_a B | 'a'\n;\nB: 'b'\n;\nT_a: 'a'\n;\n");
+        let origin = cnf.origin("S", 1).unwrap();
+        assert_eq!((origin.lhs.as_str(), origin.alt), ("S", 0));
+        assert_eq!(origin.covers, vec![0..1]);
+    }
+
+    #[test]
+    fn test_cnf_fresh_names() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' T_a;\nT_a: 'b';\n").unwrap();
+        let cnf = cfg.to_cnf();
+        assert!(cnf.cfg().is_cnf());
+        assert_eq!(cnf.cfg().to_string(), "S: T_a_1 T_a\n;\nT_a: 'b'\n;\nT_a_1: 'a'\n;\n");
+    }
+
+    #[test]
+    fn test_term_non_term() {
+        assert_eq!(term_non_term("a"), "T_a");
+        assert_eq!(term_non_term("+"), "T_x2b");
+    }
+
+    #[test]
+    fn test_remove_epsilons() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a' B;\nA: 'b' | ;\nB: A A;\n").unwrap();
+        assert!(!cfg.is_epsilon_free());
+        let eps_free = cfg.remove_epsilons();
+        assert!(eps_free.is_epsilon_free());
+        assert_eq!(eps_free.to_string(),
+                   "S: A 'a' B | 'a' B | A 'a' | 'a'\n;\nA: 'b'\n;\nB: A A | A\n;\n");
+
+        // the empty sentence is kept through a new start rule
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S | ;\n").unwrap();
+        let eps_free = cfg.remove_epsilons();
+        assert!(eps_free.is_epsilon_free());
+        assert_eq!(eps_free.to_string(), "S_start: S | \n;\nS: 'a' S | 'a'\n;\n");
+    }
+
+    #[test]
+    fn test_remove_epsilons_long_alt() {
+        // 64 nullable symbols in one alternative
+        let src = format!("%%\nS: {}'x';\nA: 'a' | ;\n", "A ".repeat(64));
+        let eps_free = Cfg::from_yacc(&src).unwrap().remove_epsilons();
+        assert!(eps_free.is_epsilon_free());
+        let alts = &eps_free.rules[0].rhs;
+        assert_eq!(alts.len(), 65);
+        assert!((0..=64).all(|n| alts.iter().any(|alt| alt.lex_symbols.len() == n + 1)));
+    }
+
+    #[test]
+    fn test_cnf_named_token() {
+        // the non-terminal for 'x' must not take the name of the token T_x
+        let cfg = Cfg::from_yacc("%token T_x\n%%\nS: T_x 'x' S | 'y';\n").unwrap();
+        assert_eq!(cfg.to_cnf().cfg().to_string(),
+                   "S_start: T_Tx5fx S_bin | 'y'\n;\nS: T_Tx5fx S_bin | 'y'\n;\n\
+                    T_Tx5fx: T_x\n;\nT_x_1: 'x'\n;\nS_bin: T_x_1 S\n;\n");
+    }
+
+    #[test]
+    fn test_empty_language() {
+        // B derives nothing, so after UNIT nor does S (though A does)
+        let cfg = Cfg::from_yacc("%%\nS: B 'a' | B;\nB: B;\nA: 'a';\n").unwrap();
+        assert!(cfg.to_cnf().cfg().rules.is_empty());
+        assert!(cfg.remove_units().rules.is_empty());
+    }
+
+    #[test]
+    fn test_remove_units() {
+        let cfg = Cfg::from_yacc("%%\nS: A | 'a' S;\nA: B | 'b';\nB: S | 'c';\n").unwrap();
+        assert!(!cfg.is_unit_free());
+        let unit_free = cfg.remove_units();
+        assert!(unit_free.is_unit_free());
+        assert_eq!(unit_free.to_string(), "S: 'a' S | 'b' | 'c'\n;\n");
+        assert!(cfg.remove_epsilons().remove_units().is_unit_free());
+    }
+
+    #[test]
+    fn test_is_cnf() {
+        assert!(Cfg::from_yacc("%%\nS: A B | ;\nA: 'a';\nB: 'b';\n").unwrap().is_cnf());
+        assert!(!Cfg::from_yacc("%%\nS: A 'b';\nA: 'a';\n").unwrap().is_cnf());
+        assert!(!Cfg::from_yacc("%%\nS: A S | 'a';\nA: 'a';\n").unwrap().is_cnf());
+    }
+}
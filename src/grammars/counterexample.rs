@@ -0,0 +1,402 @@
+//! Counterexamples explaining LR conflicts, much as Bison's
+//! `-Wcounterexamples` gives.
+//!
+//! A unifying counterexample is a sentence with two derivations, which part
+//! ways at the conflict: one takes each of the conflicting actions. They are
+//! searched for by parsing ever longer sentence prefixes with the automaton,
+//! following every action where there is a conflict. Failing that (the
+//! grammar may well be unambiguous), the counterexample is the shortest
+//! prefix of symbols reaching the conflict, with the items behind two of the
+//! conflicting actions.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use crate::grammars::Cfg;
+use crate::grammars::automaton::{GSym, LrAutomaton, LrConflict, LrKind};
+use crate::grammars::tree::DerivTree;
+
+/// Longest sentence tried for a unifying counterexample
+const MAX_SENTENCE_LEN: usize = 8;
+/// Most sentence prefixes parsed in the search
+const MAX_PREFIXES: usize = 2000;
+/// Most parses of a prefix followed
+const MAX_PARSES: usize = 64;
+/// Most conflicts one parse may pass through
+const MAX_FORKS: usize = 12;
+/// Most actions taken in parsing one token
+const MAX_STEPS: usize = 1000;
+
+/// Why a conflict is a conflict.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Counterexample {
+    /// A sentence with two derivations, each taking a different one of the
+    /// conflicting actions
+    Unifying {
+        sentence: Vec<String>,
+        derivations: (DerivTree, DerivTree),
+    },
+    /// The shortest prefix of symbols reaching the conflict state, and the
+    /// items behind two of the conflicting actions
+    NonUnifying {
+        prefix: Vec<String>,
+        items: (String, String),
+    },
+}
+
+/// A conflict and its counterexample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictExample {
+    pub conflict: LrConflict,
+    pub example: Counterexample,
+}
+
+impl ConflictExample {
+    pub fn is_unifying(&self) -> bool {
+        matches!(self.example, Counterexample::Unifying { .. })
+    }
+}
+
+impl fmt::Display for ConflictExample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.conflict)?;
+        match &self.example {
+            Counterexample::Unifying { derivations, .. } => {
+                writeln!(f, "  example: {}", derivations.0.sentence_str())?;
+                writeln!(f, "  derivation: {}", derivations.0)?;
+                writeln!(f, "  derivation: {}", derivations.1)
+            }
+            Counterexample::NonUnifying { prefix, items } => {
+                let prefix: Vec<&str> = prefix.iter().map(|sym| sym.as_str()).collect();
+                writeln!(f, "  prefix: {} . {}", prefix.join(" "), self.conflict.lookahead)?;
+                writeln!(f, "  item: {}", items.0)?;
+                writeln!(f, "  item: {}", items.1)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Action {
+    Shift(usize),
+    Reduce(usize, usize),
+    Accept,
+}
+
+/// A parse in progress, and the actions it took at each conflict.
+#[derive(Clone)]
+struct Run {
+    stack: Vec<usize>,
+    trees: Vec<DerivTree>,
+    forks: Vec<(usize, usize, Action)>,
+}
+
+impl Run {
+    /// The (state, lookahead) at which two parses of the same sentence
+    /// first took different actions.
+    fn parting(&self, other: &Run) -> Option<(usize, usize)> {
+        self.forks
+            .iter()
+            .zip(&other.forks)
+            .find(|(a, b)| a != b)
+            .map(|(a, _)| (a.0, a.1))
+    }
+}
+
+struct ExampleFinder<'a> {
+    auto: &'a LrAutomaton,
+    /// Indexed by state, then terminal
+    actions: Vec<Vec<Vec<Action>>>,
+}
+
+impl<'a> ExampleFinder<'a> {
+    fn new(auto: &'a LrAutomaton) -> Self {
+        let grm = &auto.grm;
+        let actions = auto.states
+            .iter()
+            .map(|state| {
+                let reductions = grm.reductions(&state.items);
+                (0..grm.terms.len())
+                    .map(|tidx| {
+                        let mut acts: Vec<Action> = state.gotos
+                            .iter()
+                            .filter(|(sym, _)| *sym == GSym::Term(tidx))
+                            .map(|(_, target)| Action::Shift(*target))
+                            .collect();
+                        for ((ridx, aidx), las) in &reductions {
+                            if las.contains(&tidx) {
+                                acts.push(if *ridx == grm.start_ridx() {
+                                    Action::Accept
+                                } else {
+                                    Action::Reduce(*ridx, *aidx)
+                                });
+                            }
+                        }
+                        acts
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { auto, actions }
+    }
+
+    fn goto(&self, sidx: usize, sym: GSym) -> Option<usize> {
+        self.auto.states[sidx].gotos.iter().find(|(s, _)| *s == sym).map(|(_, target)| *target)
+    }
+
+    /// The parses of `runs` having shifted the terminal `tidx` (or, for the
+    /// end marker, having accepted).
+    fn step(&self, runs: &[Run], tidx: usize) -> Vec<Run> {
+        let grm = &self.auto.grm;
+        let mut todo: Vec<Run> = runs.to_vec();
+        let mut done = Vec::<Run>::new();
+        let mut seen = HashSet::<(Vec<usize>, Vec<(usize, usize, Action)>)>::new();
+        let mut steps = 0;
+        while let Some(run) = todo.pop() {
+            steps += 1;
+            if steps > MAX_STEPS || done.len() >= MAX_PARSES {
+                break;
+            }
+            let top = *run.stack.last().expect("the stack holds the start state");
+            let acts = &self.actions[top][tidx];
+            if acts.len() > 1 && run.forks.len() >= MAX_FORKS {
+                continue;
+            }
+            for act in acts {
+                let mut run = run.clone();
+                if acts.len() > 1 {
+                    run.forks.push((top, tidx, *act));
+                }
+                match *act {
+                    Action::Shift(target) => {
+                        run.stack.push(target);
+                        let tok = grm.terms[tidx].to_string();
+                        let named = grm.named.contains(&tok);
+                        run.trees.push(DerivTree::Term { tok, named });
+                        if seen.insert((run.stack.clone(), run.forks.clone())) {
+                            done.push(run);
+                        }
+                    }
+                    Action::Accept => done.push(run),
+                    Action::Reduce(ridx, aidx) => {
+                        let n = grm.alts[ridx][aidx].len();
+                        run.stack.truncate(run.stack.len() - n);
+                        let children = run.trees.split_off(run.trees.len() - n);
+                        run.trees.push(DerivTree::NonTerm { lhs: grm.lhss[ridx].to_string(), alt: aidx, children });
+                        let from = *run.stack.last().expect("the stack holds the start state");
+                        if let Some(target) = self.goto(from, GSym::NonTerm(ridx)) {
+                            run.stack.push(target);
+                            todo.push(run);
+                        }
+                    }
+                }
+            }
+        }
+
+        done
+    }
+
+    /// Search the sentences, shortest first, for one with two parses parting
+    /// at each of `at` (a state and lookahead).
+    fn unifying(&self, at: &[(usize, usize)]) -> Vec<Option<Counterexample>> {
+        let grm = &self.auto.grm;
+        let mut found: Vec<Option<Counterexample>> = vec![None; at.len()];
+        let start = Run { stack: vec![0], trees: vec![], forks: vec![] };
+        let mut queue: VecDeque<(Vec<usize>, Vec<Run>)> = VecDeque::new();
+        queue.push_back((vec![], vec![start]));
+        let mut parsed = 0;
+        while let Some((prefix, runs)) = queue.pop_front() {
+            if found.iter().all(Option::is_some) {
+                break;
+            }
+            let accepted = self.step(&runs, grm.end_tidx());
+            // of the pairs of parses parting at a conflict, the smallest
+            let mut pairs: Vec<Option<(usize, &Run, &Run)>> = vec![None; at.len()];
+            for (i, a) in accepted.iter().enumerate() {
+                for b in &accepted[i + 1..] {
+                    let parting = match a.parting(b) {
+                        Some(parting) => parting,
+                        None => continue,
+                    };
+                    let size = tree_size(&a.trees[0]) + tree_size(&b.trees[0]);
+                    for (cidx, _) in at.iter().enumerate().filter(|(_, at)| **at == parting) {
+                        if found[cidx].is_none() && pairs[cidx].as_ref().map_or(true, |(best, _, _)| size < *best) {
+                            pairs[cidx] = Some((size, a, b));
+                        }
+                    }
+                }
+            }
+            for (cidx, pair) in pairs.into_iter().enumerate() {
+                let (a, b) = match pair {
+                    Some((_, a, b)) => (a, b),
+                    None => continue,
+                };
+                // the derivation taking the first action first
+                let acts = &self.actions[at[cidx].0][at[cidx].1];
+                let rank = |run: &Run| {
+                    let fork = run.forks.iter().find(|fork| (fork.0, fork.1) == at[cidx]);
+                    fork.and_then(|fork| acts.iter().position(|act| *act == fork.2))
+                };
+                let (a, b) = if rank(a) <= rank(b) { (a, b) } else { (b, a) };
+                found[cidx] = Some(Counterexample::Unifying {
+                    sentence: prefix.iter().map(|tidx| grm.terms[*tidx].to_string()).collect(),
+                    derivations: (a.trees[0].clone(), b.trees[0].clone()),
+                });
+            }
+            if prefix.len() == MAX_SENTENCE_LEN {
+                continue;
+            }
+            for tidx in 0..grm.end_tidx() {
+                if parsed + queue.len() >= MAX_PREFIXES {
+                    break;
+                }
+                let next = self.step(&runs, tidx);
+                if !next.is_empty() {
+                    let mut prefix = prefix.clone();
+                    prefix.push(tidx);
+                    queue.push_back((prefix, next));
+                }
+            }
+            parsed += 1;
+        }
+
+        found
+    }
+
+    /// The shortest prefix of symbols taking the automaton to state `sidx`.
+    fn shortest_prefix(&self, sidx: usize) -> Vec<String> {
+        let mut parents: Vec<Option<(usize, GSym)>> = vec![None; self.auto.states.len()];
+        let mut queue = VecDeque::from(vec![0]);
+        while let Some(from) = queue.pop_front() {
+            for (sym, target) in &self.auto.states[from].gotos {
+                if *target != 0 && parents[*target].is_none() {
+                    parents[*target] = Some((from, *sym));
+                    queue.push_back(*target);
+                }
+            }
+        }
+        let mut prefix = Vec::<String>::new();
+        let mut sidx = sidx;
+        while let Some((from, sym)) = parents[sidx] {
+            prefix.push(self.auto.grm.sym_name(sym));
+            sidx = from;
+        }
+        prefix.reverse();
+        prefix
+    }
+
+    /// The item behind an action in state `sidx` on terminal `tidx`.
+    fn action_item(&self, sidx: usize, tidx: usize, act: Action) -> String {
+        let grm = &self.auto.grm;
+        match act {
+            Action::Shift(_) => {
+                let item = self.auto.states[sidx].items
+                    .iter()
+                    .find(|item| grm.next_sym(item) == Some(GSym::Term(tidx)))
+                    .expect("a shift has an item");
+                grm.item_str(item.ridx, item.aidx, item.dot)
+            }
+            Action::Reduce(ridx, aidx) => grm.item_str(ridx, aidx, grm.alts[ridx][aidx].len()),
+            Action::Accept => grm.item_str(grm.start_ridx(), 0, 1),
+        }
+    }
+
+    fn non_unifying(&self, (sidx, tidx): (usize, usize)) -> Counterexample {
+        let acts = &self.actions[sidx][tidx];
+        Counterexample::NonUnifying {
+            prefix: self.shortest_prefix(sidx),
+            items: (self.action_item(sidx, tidx, acts[0]), self.action_item(sidx, tidx, acts[1])),
+        }
+    }
+}
+
+fn tree_size(tree: &DerivTree) -> usize {
+    match tree {
+        DerivTree::NonTerm { children, .. } => 1 + children.iter().map(tree_size).sum::<usize>(),
+        DerivTree::Term { .. } => 1,
+    }
+}
+
+impl LrAutomaton {
+    /// A counterexample for each conflict.
+    pub fn counterexamples(&self) -> Vec<ConflictExample> {
+        let conflicts = self.conflicts();
+        if conflicts.is_empty() {
+            return vec![];
+        }
+        let at: Vec<(usize, usize)> = conflicts
+            .iter()
+            .map(|conflict| {
+                let tidx = (0..self.grm.terms.len())
+                    .find(|tidx| self.grm.sym_name(GSym::Term(*tidx)) == conflict.lookahead)
+                    .expect("a conflict is on a terminal");
+                (conflict.state, tidx)
+            })
+            .collect();
+        let finder = ExampleFinder::new(self);
+        let unifying = finder.unifying(&at);
+        conflicts
+            .into_iter()
+            .zip(unifying)
+            .zip(at)
+            .map(|((conflict, unifying), at)| ConflictExample {
+                conflict,
+                example: unifying.unwrap_or_else(|| finder.non_unifying(at)),
+            })
+            .collect()
+    }
+}
+
+impl Cfg {
+    /// A counterexample for each conflict in the CFG's LR(1) automaton.
+    pub fn conflict_examples(&self) -> Vec<ConflictExample> {
+        self.lr_automaton(LrKind::Lr1).counterexamples()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unifying() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' E | 'x';\n").unwrap();
+        let examples = cfg.conflict_examples();
+        assert_eq!(examples.len(), 1);
+        assert!(examples[0].is_unifying());
+        assert_eq!(examples[0].to_string(),
+                   "state 4: shift/reduce conflict on '+'\n  \
+                    example: 'x' '+' 'x' '+' 'x'\n  \
+                    derivation: (E (E 'x') '+' (E (E 'x') '+' (E 'x')))\n  \
+                    derivation: (E (E (E 'x') '+' (E 'x')) '+' (E 'x'))\n");
+        if let Counterexample::Unifying { derivations, .. } = &examples[0].example {
+            assert!(derivations.0.is_derivation_of(&cfg) && derivations.1.is_derivation_of(&cfg));
+        }
+
+        // named tokens are shown bare
+        let cfg = Cfg::from_yacc("%token ID\n%%\nE: E '+' E | ID;\n").unwrap();
+        assert_eq!(cfg.conflict_examples()[0].to_string(),
+                   "state 4: shift/reduce conflict on '+'\n  \
+                    example: ID '+' ID '+' ID\n  \
+                    derivation: (E (E ID) '+' (E (E ID) '+' (E ID)))\n  \
+                    derivation: (E (E (E ID) '+' (E ID)) '+' (E ID))\n");
+    }
+
+    #[test]
+    fn test_non_unifying() {
+        // LR(2), but not ambiguous
+        let cfg = Cfg::from_yacc("%%\nS: 'a' A 'b' 'c' | 'a' B 'b' 'd';\nA: 'x';\nB: 'x';\n").unwrap();
+        let examples = cfg.conflict_examples();
+        assert_eq!(examples.len(), 1);
+        assert!(!examples[0].is_unifying());
+        assert_eq!(examples[0].example, Counterexample::NonUnifying {
+            prefix: vec!["'a'".to_string(), "'x'".to_string()],
+            items: ("A: 'x' .".to_string(), "B: 'x' .".to_string()),
+        });
+        assert!(examples[0].to_string().contains("prefix: 'a' 'x' . 'b'\n"));
+
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S 'b' | ;\n").unwrap();
+        assert!(cfg.conflict_examples().is_empty());
+    }
+}
@@ -0,0 +1,353 @@
+//! Test suites of sentences which together exercise a CFG, after Purdom's
+//! "A sentence generator for testing parsers" (1972).
+//!
+//! Every sentence is built around one item still to be covered: the
+//! cheapest context takes the start symbol to the item's rule, the item
+//! fixes the alternatives there, and every other non-terminal takes its
+//! smallest derivation. Each sentence covers every item in its derivation,
+//! and sentences left covering nothing new are dropped at the end, so the
+//! suite is small, though not necessarily the smallest possible.
+
+use std::collections::HashSet;
+
+use crate::grammars::Cfg;
+use crate::grammars::sample::Sample;
+use crate::grammars::transform::{IdxRules, Sym};
+use crate::grammars::tree::DerivTree;
+
+/// What a test suite must exercise.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Coverage {
+    /// Every alternative of every rule
+    Alts,
+    /// Every alternative of a non-terminal at every place it is used: for
+    /// each alternative, each non-terminal in it, and each alternative of
+    /// that non-terminal
+    Pairs,
+}
+
+/// A set of sentences covering a CFG.
+#[derive(Debug, Clone)]
+pub struct TestSuite {
+    pub samples: Vec<Sample>,
+    /// The items covered by `samples`
+    pub covered: usize,
+    /// All the items. Those using unproductive or unreachable non-terminals
+    /// cannot be covered.
+    pub total: usize,
+}
+
+/// Something to cover: an alternative, or a pair of an alternative and the
+/// alternative taken by the non-terminal at one of its positions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Item {
+    Alt(usize, usize),
+    Pair((usize, usize), usize, usize),
+}
+
+/// How the parent derives a non-terminal in the cheapest context for it:
+/// rule, alternative and position.
+type Context = (usize, usize, usize);
+
+struct SuiteBuilder {
+    rules: IdxRules,
+    /// For each rule, the size of its smallest derivation tree and the
+    /// alternative giving it
+    smallest: Vec<Option<(usize, usize)>>,
+    /// For each rule other than the start rule, the context through which
+    /// it is cheapest to reach, if it is reachable
+    contexts: Vec<Option<Context>>,
+}
+
+impl SuiteBuilder {
+    fn new(cfg: &Cfg) -> Self {
+        let rules = IdxRules::new(cfg);
+        let smallest = smallest(&rules);
+        let contexts = contexts(&rules, &smallest);
+
+        Self { rules, smallest, contexts }
+    }
+
+    fn alt_size(&self, alt: &[Sym]) -> Option<usize> {
+        alt_size(alt, &self.smallest)
+    }
+
+    fn is_reachable(&self, ridx: usize) -> bool {
+        ridx == 0 || self.contexts[ridx].is_some()
+    }
+
+    fn items(&self, coverage: Coverage) -> Vec<Item> {
+        let mut items = Vec::<Item>::new();
+        for (ridx, alts) in self.rules.alts.iter().enumerate() {
+            for (aidx, alt) in alts.iter().enumerate() {
+                items.push(Item::Alt(ridx, aidx));
+                if coverage == Coverage::Alts {
+                    continue;
+                }
+                for (sidx, sym) in alt.iter().enumerate() {
+                    if let Sym::NonTerm(Some(nt)) = sym {
+                        for nt_aidx in 0..self.rules.alts[*nt].len() {
+                            items.push(Item::Pair((ridx, aidx), sidx, nt_aidx));
+                        }
+                    }
+                }
+            }
+        }
+
+        items
+    }
+
+    fn is_coverable(&self, item: &Item) -> bool {
+        match *item {
+            Item::Alt(ridx, aidx) => self.is_reachable(ridx) && self.alt_size(&self.rules.alts[ridx][aidx]).is_some(),
+            Item::Pair((ridx, aidx), sidx, nt_aidx) => {
+                let nt = match self.rules.alts[ridx][aidx][sidx] {
+                    Sym::NonTerm(Some(nt)) => nt,
+                    _ => unreachable!("pairs are only made for defined non-terminals"),
+                };
+                self.is_coverable(&Item::Alt(ridx, aidx)) && self.alt_size(&self.rules.alts[nt][nt_aidx]).is_some()
+            }
+        }
+    }
+
+    /// The smallest tree for the productive `ridx`.
+    fn smallest_tree(&self, ridx: usize) -> DerivTree {
+        let (_, aidx) = self.smallest[ridx].expect("only productive rules are expanded");
+        self.alt_tree(ridx, aidx, None)
+    }
+
+    /// A tree using alternative `aidx` for `ridx`, with `child` (if given)
+    /// at its position and the smallest trees elsewhere.
+    fn alt_tree(&self, ridx: usize, aidx: usize, child: Option<(usize, DerivTree)>) -> DerivTree {
+        let (child_sidx, mut child) = match child {
+            Some((sidx, tree)) => (Some(sidx), Some(tree)),
+            None => (None, None),
+        };
+        let children = self.rules.alts[ridx][aidx]
+            .iter()
+            .enumerate()
+            .map(|(sidx, sym)| match sym {
+                _ if Some(sidx) == child_sidx => child.take().expect("the child is used once"),
+                Sym::Term(tok) => DerivTree::Term { tok: tok.to_string(), named: self.rules.named.contains(tok) },
+                Sym::NonTerm(nt) => self.smallest_tree(nt.expect("productive alternatives use defined rules")),
+            })
+            .collect();
+
+        DerivTree::NonTerm { lhs: self.rules.lhss[ridx].to_string(), alt: aidx, children }
+    }
+
+    /// A tree for the start rule, with `tree` for `ridx` in the cheapest context.
+    fn in_context(&self, mut ridx: usize, mut tree: DerivTree) -> DerivTree {
+        while let Some((parent, aidx, sidx)) = self.contexts[ridx] {
+            tree = self.alt_tree(parent, aidx, Some((sidx, tree)));
+            ridx = parent;
+        }
+
+        tree
+    }
+
+    fn covering_tree(&self, item: &Item) -> DerivTree {
+        match *item {
+            Item::Alt(ridx, aidx) => self.in_context(ridx, self.alt_tree(ridx, aidx, None)),
+            Item::Pair((ridx, aidx), sidx, nt_aidx) => {
+                let nt = match self.rules.alts[ridx][aidx][sidx] {
+                    Sym::NonTerm(Some(nt)) => nt,
+                    _ => unreachable!("pairs are only made for defined non-terminals"),
+                };
+                let nt_tree = self.alt_tree(nt, nt_aidx, None);
+                self.in_context(ridx, self.alt_tree(ridx, aidx, Some((sidx, nt_tree))))
+            }
+        }
+    }
+
+    /// The items covered by `tree`.
+    fn covers(&self, tree: &DerivTree, coverage: Coverage, items: &mut HashSet<Item>) {
+        if let DerivTree::NonTerm { lhs, alt, children } = tree {
+            let ridx = self.rules.lhss.iter().position(|l| l == lhs).expect("trees use the rules");
+            items.insert(Item::Alt(ridx, *alt));
+            for (sidx, child) in children.iter().enumerate() {
+                if let DerivTree::NonTerm { alt: child_alt, .. } = child {
+                    if coverage == Coverage::Pairs {
+                        items.insert(Item::Pair((ridx, *alt), sidx, *child_alt));
+                    }
+                }
+                self.covers(child, coverage, items);
+            }
+        }
+    }
+
+    fn build(&self, coverage: Coverage) -> TestSuite {
+        let items = self.items(coverage);
+        let coverable: Vec<&Item> = items.iter().filter(|item| self.is_coverable(item)).collect();
+        let mut covered = HashSet::<Item>::new();
+        let mut suite = Vec::<(DerivTree, HashSet<Item>)>::new();
+        for item in &coverable {
+            if covered.contains(item) {
+                continue;
+            }
+            let tree = self.covering_tree(item);
+            let mut tree_items = HashSet::<Item>::new();
+            self.covers(&tree, coverage, &mut tree_items);
+            covered.extend(tree_items.iter().cloned());
+            suite.push((tree, tree_items));
+        }
+
+        // drop the sentences whose items the others all cover
+        let mut i = 0;
+        while i < suite.len() {
+            let others: HashSet<&Item> = suite
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .flat_map(|(_, (_, items))| items.iter())
+                .collect();
+            if suite[i].1.iter().all(|item| others.contains(item)) {
+                suite.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        TestSuite {
+            samples: suite
+                .into_iter()
+                .map(|(tree, _)| Sample { sentence: tree.sentence(), tree })
+                .collect(),
+            covered: coverable.len(),
+            total: items.len(),
+        }
+    }
+}
+
+fn alt_size(alt: &[Sym], smallest: &[Option<(usize, usize)>]) -> Option<usize> {
+    let mut size = 1;
+    for sym in alt {
+        size += match sym {
+            Sym::Term(_) => 1,
+            Sym::NonTerm(ridx) => (*ridx).and_then(|ridx| smallest[ridx])?.0,
+        };
+    }
+    Some(size)
+}
+
+/// For each rule, the size of its smallest derivation tree and the
+/// alternative giving it, or `None` if it derives no sentence. As the size
+/// of a tree is more than that of its subtrees, following these
+/// alternatives always ends.
+fn smallest(rules: &IdxRules) -> Vec<Option<(usize, usize)>> {
+    let mut smallest = vec![None; rules.lhss.len()];
+    loop {
+        let mut changed = false;
+        for ridx in 0..rules.lhss.len() {
+            for (aidx, alt) in rules.alts[ridx].iter().enumerate() {
+                if let Some(size) = alt_size(alt, &smallest) {
+                    if smallest[ridx].map_or(true, |(s, _)| size < s) {
+                        smallest[ridx] = Some((size, aidx));
+                        changed = true;
+                    }
+                }
+            }
+        }
+        if !changed {
+            return smallest;
+        }
+    }
+}
+
+/// For each rule, the cheapest context to reach it in from the start rule,
+/// counting the size of the trees needed around it.
+fn contexts(rules: &IdxRules, smallest: &[Option<(usize, usize)>]) -> Vec<Option<Context>> {
+    let n = rules.lhss.len();
+    let mut costs: Vec<Option<usize>> = vec![None; n];
+    let mut contexts: Vec<Option<Context>> = vec![None; n];
+    if n == 0 {
+        return contexts;
+    }
+    costs[0] = Some(0);
+    loop {
+        let mut changed = false;
+        for ridx in 0..n {
+            let cost = match costs[ridx] {
+                Some(cost) => cost,
+                None => continue,
+            };
+            for (aidx, alt) in rules.alts[ridx].iter().enumerate() {
+                let alt_size = match alt_size(alt, smallest) {
+                    Some(size) => size,
+                    None => continue,
+                };
+                for (sidx, sym) in alt.iter().enumerate() {
+                    if let Sym::NonTerm(Some(nt)) = sym {
+                        let nt_size = smallest[*nt].expect("productive alternatives use productive rules").0;
+                        let nt_cost = cost + alt_size - nt_size;
+                        if *nt != 0 && costs[*nt].map_or(true, |c| nt_cost < c) {
+                            costs[*nt] = Some(nt_cost);
+                            contexts[*nt] = Some((ridx, aidx, sidx));
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        if !changed {
+            return contexts;
+        }
+    }
+}
+
+impl Cfg {
+    /// A small set of sentences which together give `coverage` of the CFG.
+    pub fn test_suite(&self, coverage: Coverage) -> TestSuite {
+        SuiteBuilder::new(self).build(coverage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> Cfg {
+        Cfg::from_yacc("%%\nS: 'a' A B | 'b' S;\nA: 'x' | 'y' | ;\nB: 'z' | A 'w';\nC: 'c';\n").unwrap()
+    }
+
+    fn all_alts(cfg: &Cfg, suite: &TestSuite) -> HashSet<(String, usize)> {
+        fn walk(tree: &DerivTree, alts: &mut HashSet<(String, usize)>) {
+            if let DerivTree::NonTerm { lhs, alt, children } = tree {
+                alts.insert((lhs.to_string(), *alt));
+                children.iter().for_each(|c| walk(c, alts));
+            }
+        }
+        let mut alts = HashSet::new();
+        for sample in &suite.samples {
+            assert_eq!(sample.sentence, sample.tree.sentence());
+            walk(&sample.tree, &mut alts);
+        }
+        assert!(cfg.rules.iter().all(|rule| rule.lhs == "C" ||
+            (0..rule.rhs.len()).all(|i| alts.contains(&(rule.lhs.to_string(), i)))));
+        alts
+    }
+
+    #[test]
+    fn test_alt_coverage() {
+        let cfg = cfg();
+        let suite = cfg.test_suite(Coverage::Alts);
+        all_alts(&cfg, &suite);
+        // C is unreachable
+        assert_eq!((suite.covered, suite.total), (7, 8));
+        let sentences: Vec<String> = suite.samples.iter().map(|s| s.sentence.concat()).collect();
+        assert_eq!(sentences, vec!["baz", "axz", "ayz", "aw"]);
+    }
+
+    #[test]
+    fn test_pair_coverage() {
+        let cfg = cfg();
+        let suite = cfg.test_suite(Coverage::Pairs);
+        all_alts(&cfg, &suite);
+        // A three ways in each of `S: 'a' A B` and `B: A 'w'`, B two ways,
+        // and S two ways in `S: 'b' S`
+        assert_eq!(suite.covered, 7 + 3 + 2 + 3 + 2);
+        assert!(suite.samples.len() >= 3);
+        let sentences: HashSet<String> = suite.samples.iter().map(|s| s.sentence.concat()).collect();
+        assert!(sentences.iter().any(|s| s.contains("yw")));
+    }
+}
@@ -0,0 +1,476 @@
+//! An Earley parser, which handles any CFG: ambiguous, left-recursive, with
+//! empty alternatives or with cycles.
+//!
+//! Recognition builds the usual Earley sets, using Aycock and Horspool's
+//! trick of moving the dot over nullable non-terminals as soon as they are
+//! predicted. A shared packed parse forest is then read back from the sets:
+//! there is a node for each symbol and span it derives in some parse of the
+//! sentence, and each way the node is derived is packed under it as the
+//! alternative used and the nodes for its symbols. Nodes are shared between
+//! parses, so the forest stays finite even when there are infinitely many
+//! parses; these show up as cycles in it.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::grammars::{Cfg, LexSymbol};
+use crate::grammars::enumerate::DerivCount;
+use crate::grammars::transform::{IdxRules, nullable, Sym};
+use crate::grammars::tree::{DerivTree, trees_to_dot, trees_to_json, trees_to_sexp};
+
+/// An Earley item: the alternative `aidx` of rule `ridx`, with the dot
+/// before symbol `dot`, started at position `origin` of the sentence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct Item {
+    ridx: usize,
+    aidx: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Item {
+    fn advance(self) -> Item {
+        Item { dot: self.dot + 1, ..self }
+    }
+}
+
+/// The Earley sets for a sentence.
+struct Chart {
+    sets: Vec<Vec<Item>>,
+    seen: Vec<HashSet<Item>>,
+}
+
+impl Chart {
+    fn new(len: usize) -> Self {
+        Self {
+            sets: vec![Vec::new(); len + 1],
+            seen: vec![HashSet::new(); len + 1],
+        }
+    }
+
+    fn add(&mut self, pos: usize, item: Item) {
+        if self.seen[pos].insert(item) {
+            self.sets[pos].push(item);
+        }
+    }
+
+    fn contains(&self, pos: usize, item: &Item) -> bool {
+        self.seen[pos].contains(item)
+    }
+}
+
+/// Parses sentences of a CFG.
+pub struct EarleyParser {
+    rules: IdxRules,
+    nullable: Vec<bool>,
+}
+
+impl EarleyParser {
+    fn new(cfg: &Cfg) -> Self {
+        let rules = IdxRules::new(cfg);
+        let nullable = {
+            let rules: Vec<(&str, Vec<&[LexSymbol]>)> = cfg.rules
+                .iter()
+                .map(|rule| (rule.lhs.as_str(), rule.rhs.iter().map(|alt| alt.lex_symbols.as_slice()).collect()))
+                .collect();
+            nullable(&rules)
+        };
+        let nullable = rules.lhss.iter().map(|lhs| nullable.contains(lhs)).collect();
+
+        Self { rules, nullable }
+    }
+
+    fn chart(&self, sentence: &[String]) -> Chart {
+        let mut chart = Chart::new(sentence.len());
+        if self.rules.lhss.is_empty() {
+            return chart;
+        }
+        for aidx in 0..self.rules.alts[0].len() {
+            chart.add(0, Item { ridx: 0, aidx, dot: 0, origin: 0 });
+        }
+        for pos in 0..=sentence.len() {
+            let mut i = 0;
+            while let Some(item) = chart.sets[pos].get(i).cloned() {
+                match self.rules.alts[item.ridx][item.aidx].get(item.dot) {
+                    // complete
+                    None => {
+                        let mut j = 0;
+                        while let Some(waiting) = chart.sets[item.origin].get(j).cloned() {
+                            if let Some(Sym::NonTerm(Some(nt))) = self.rules.alts[waiting.ridx][waiting.aidx].get(waiting.dot) {
+                                if *nt == item.ridx {
+                                    chart.add(pos, waiting.advance());
+                                }
+                            }
+                            j += 1;
+                        }
+                    }
+                    // predict
+                    Some(Sym::NonTerm(Some(nt))) => {
+                        for aidx in 0..self.rules.alts[*nt].len() {
+                            chart.add(pos, Item { ridx: *nt, aidx, dot: 0, origin: pos });
+                        }
+                        if self.nullable[*nt] {
+                            chart.add(pos, item.advance());
+                        }
+                    }
+                    Some(Sym::NonTerm(None)) => (),
+                    // scan
+                    Some(Sym::Term(tok)) => {
+                        if sentence.get(pos) == Some(tok) {
+                            chart.add(pos + 1, item.advance());
+                        }
+                    }
+                }
+                i += 1;
+            }
+        }
+
+        chart
+    }
+
+    fn accepting(&self, chart: &Chart, len: usize) -> bool {
+        !self.rules.lhss.is_empty() && (0..self.rules.alts[0].len()).any(|aidx| {
+            let dot = self.rules.alts[0][aidx].len();
+            chart.contains(len, &Item { ridx: 0, aidx, dot, origin: 0 })
+        })
+    }
+
+    /// Is `sentence` in the language?
+    pub fn recognise(&self, sentence: &[String]) -> bool {
+        self.accepting(&self.chart(sentence), sentence.len())
+    }
+
+    /// All the parses of `sentence`, or `None` if it is not in the language.
+    pub fn parse(&self, sentence: &[String]) -> Option<ParseForest> {
+        let chart = self.chart(sentence);
+        if !self.accepting(&chart, sentence.len()) {
+            return None;
+        }
+        let mut builder = ForestBuilder {
+            parser: self,
+            chart: &chart,
+            sentence,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+        };
+        let root = builder.non_term(0, 0, sentence.len());
+
+        Some(ParseForest { nodes: builder.nodes, root, named: self.rules.named.clone() })
+    }
+}
+
+/// The symbol a forest node derives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForestSym {
+    NonTerm(String),
+    Term(String),
+}
+
+/// One way of deriving a forest node: the alternative used, and the nodes
+/// for each of its symbols.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedNode {
+    pub alt: usize,
+    pub children: Vec<usize>,
+}
+
+/// A symbol deriving the tokens from `start` up to `end`, in every way it
+/// does so in some parse. Terminal nodes have no packed nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForestNode {
+    pub sym: ForestSym,
+    pub start: usize,
+    pub end: usize,
+    pub packed: Vec<PackedNode>,
+}
+
+/// A shared packed parse forest, with nodes referred to by their index.
+#[derive(Debug, Clone)]
+pub struct ParseForest {
+    nodes: Vec<ForestNode>,
+    root: usize,
+    /// The terminals which are named tokens
+    named: HashSet<String>,
+}
+
+impl ParseForest {
+    pub fn nodes(&self) -> &[ForestNode] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    /// Does the sentence have more than one parse?
+    pub fn is_ambiguous(&self) -> bool {
+        self.count_trees() != DerivCount::Finite(1)
+    }
+
+    /// The number of parses, which is infinite if the forest has a cycle.
+    pub fn count_trees(&self) -> DerivCount {
+        fn count(forest: &ParseForest, id: usize, on_path: &mut Vec<bool>,
+                 counts: &mut HashMap<usize, DerivCount>) -> DerivCount {
+            if on_path[id] {
+                return DerivCount::Infinite;
+            }
+            if let Some(c) = counts.get(&id) {
+                return *c;
+            }
+            let node = &forest.nodes[id];
+            if node.packed.is_empty() {
+                return DerivCount::Finite(1);
+            }
+            on_path[id] = true;
+            let mut total = DerivCount::Finite(0);
+            for packed in &node.packed {
+                let mut ways = DerivCount::Finite(1);
+                for child in &packed.children {
+                    ways = ways.mul(count(forest, *child, on_path, counts));
+                }
+                total = total.add(ways);
+            }
+            on_path[id] = false;
+            counts.insert(id, total);
+            total
+        }
+
+        count(self, self.root, &mut vec![false; self.nodes.len()], &mut HashMap::new())
+    }
+
+    /// Up to `max` of the parses which use no node inside itself (so
+    /// leaving out the infinitely many a cycle gives rise to).
+    pub fn trees(&self, max: usize) -> Vec<DerivTree> {
+        self.node_trees(self.root, max, &mut vec![false; self.nodes.len()])
+    }
+
+    /// Up to `max` parses side by side in one DOT graph.
+    pub fn to_dot(&self, max: usize) -> String {
+        trees_to_dot(&self.trees(max))
+    }
+
+    /// Up to `max` parses as S-expressions, one per line.
+    pub fn to_sexp(&self, max: usize) -> String {
+        trees_to_sexp(&self.trees(max))
+    }
+
+    /// Up to `max` parses as a JSON array.
+    pub fn to_json(&self, max: usize) -> String {
+        trees_to_json(&self.trees(max))
+    }
+
+    fn node_trees(&self, id: usize, max: usize, on_path: &mut Vec<bool>) -> Vec<DerivTree> {
+        let node = &self.nodes[id];
+        let lhs = match &node.sym {
+            ForestSym::Term(tok) => {
+                return vec![DerivTree::Term { tok: tok.to_string(), named: self.named.contains(tok) }];
+            }
+            ForestSym::NonTerm(lhs) => lhs,
+        };
+        if on_path[id] {
+            return vec![];
+        }
+        on_path[id] = true;
+        let mut trees = Vec::<DerivTree>::new();
+        for packed in &node.packed {
+            let mut child_lists: Vec<Vec<DerivTree>> = vec![vec![]];
+            for child in &packed.children {
+                let child_trees = self.node_trees(*child, max, on_path);
+                child_lists = child_lists
+                    .iter()
+                    .flat_map(|list| child_trees.iter().map(move |tree| {
+                        let mut list = list.clone();
+                        list.push(tree.clone());
+                        list
+                    }))
+                    .take(max)
+                    .collect();
+            }
+            trees.extend(child_lists
+                .into_iter()
+                .map(|children| DerivTree::NonTerm { lhs: lhs.to_string(), alt: packed.alt, children }));
+            if trees.len() >= max {
+                trees.truncate(max);
+                break;
+            }
+        }
+        on_path[id] = false;
+
+        trees
+    }
+}
+
+/// Reads the forest back from the chart.
+struct ForestBuilder<'a> {
+    parser: &'a EarleyParser,
+    chart: &'a Chart,
+    sentence: &'a [String],
+    nodes: Vec<ForestNode>,
+    ids: HashMap<(Option<usize>, usize, usize), usize>,
+}
+
+impl ForestBuilder<'_> {
+    fn term(&mut self, pos: usize) -> usize {
+        let key = (None, pos, pos + 1);
+        if let Some(id) = self.ids.get(&key) {
+            return *id;
+        }
+        self.nodes.push(ForestNode {
+            sym: ForestSym::Term(self.sentence[pos].to_string()),
+            start: pos,
+            end: pos + 1,
+            packed: vec![],
+        });
+        self.ids.insert(key, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// The node for rule `ridx` deriving `start..end`, which some parse uses.
+    fn non_term(&mut self, ridx: usize, start: usize, end: usize) -> usize {
+        let key = (Some(ridx), start, end);
+        if let Some(id) = self.ids.get(&key) {
+            return *id;
+        }
+        // add the node before its children, which may lead back to it
+        let id = self.nodes.len();
+        self.nodes.push(ForestNode {
+            sym: ForestSym::NonTerm(self.parser.rules.lhss[ridx].to_string()),
+            start,
+            end,
+            packed: vec![],
+        });
+        self.ids.insert(key, id);
+
+        let mut packed = Vec::<PackedNode>::new();
+        for aidx in 0..self.parser.rules.alts[ridx].len() {
+            let len = self.parser.rules.alts[ridx][aidx].len();
+            let completed = Item { ridx, aidx, dot: len, origin: start };
+            if !self.chart.contains(end, &completed) {
+                continue;
+            }
+            for children in self.children(completed, end) {
+                packed.push(PackedNode { alt: aidx, children });
+            }
+        }
+        self.nodes[id].packed = packed;
+
+        id
+    }
+
+    /// For `item` in the set at `end`, each way the symbols before its dot
+    /// derive the tokens from its origin up to `end`, as the nodes for them.
+    fn children(&mut self, item: Item, end: usize) -> Vec<Vec<usize>> {
+        if item.dot == 0 {
+            return if item.origin == end { vec![vec![]] } else { vec![] };
+        }
+        let prev = Item { dot: item.dot - 1, ..item };
+        let sym = self.parser.rules.alts[item.ridx][item.aidx][item.dot - 1].clone();
+        let mut all = Vec::<Vec<usize>>::new();
+        for mid in item.origin..=end {
+            if !self.chart.contains(mid, &prev) {
+                continue;
+            }
+            let child = match &sym {
+                Sym::Term(tok) => {
+                    if mid + 1 != end || self.sentence[mid] != *tok {
+                        continue;
+                    }
+                    self.term(mid)
+                }
+                Sym::NonTerm(None) => continue,
+                Sym::NonTerm(Some(nt)) => {
+                    let derives = (0..self.parser.rules.alts[*nt].len()).any(|aidx| {
+                        let dot = self.parser.rules.alts[*nt][aidx].len();
+                        self.chart.contains(end, &Item { ridx: *nt, aidx, dot, origin: mid })
+                    });
+                    if !derives {
+                        continue;
+                    }
+                    self.non_term(*nt, mid, end)
+                }
+            };
+            for mut prefix in self.children(prev, mid) {
+                prefix.push(child);
+                all.push(prefix);
+            }
+        }
+
+        all
+    }
+}
+
+impl Cfg {
+    pub fn earley(&self) -> EarleyParser {
+        EarleyParser::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammars::test_utils::toks;
+
+    use super::*;
+
+    #[test]
+    fn test_recognise() {
+        // left-recursive, with an empty alternative
+        let cfg = Cfg::from_yacc("%%\nS: S 'a' | B;\nB: 'b' | ;\n").unwrap();
+        let parser = cfg.earley();
+        for s in &["", "b", "a", "baaa", "aa"] {
+            assert!(parser.recognise(&toks(s)), "{}", s);
+        }
+        for s in &["ab", "bb", "c"] {
+            assert!(!parser.recognise(&toks(s)), "{}", s);
+        }
+        assert!(cfg.enumerate(5).sentences().iter().all(|s| parser.recognise(s)));
+    }
+
+    #[test]
+    fn test_parse_ambiguous() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' E | 'x';\n").unwrap();
+        let parser = cfg.earley();
+        let forest = parser.parse(&toks("x+x+x")).unwrap();
+        assert!(forest.is_ambiguous());
+        assert_eq!(forest.count_trees(), DerivCount::Finite(2));
+        let trees = forest.trees(10);
+        assert_eq!(trees.len(), 2);
+        assert_ne!(trees[0], trees[1]);
+        assert!(trees.iter().all(|t| t.is_derivation_of(&cfg)));
+        assert_eq!(forest.to_sexp(10), "(E (E 'x') '+' (E (E 'x') '+' (E 'x')))\n(E (E (E 'x') '+' (E 'x')) '+' (E 'x'))\n");
+        assert!(trees.iter().all(|t| t.sentence() == toks("x+x+x")));
+        // the nodes for "x+x" at either end are shared
+        let root = &forest.nodes()[forest.root()];
+        assert_eq!((root.start, root.end, root.packed.len()), (0, 5, 2));
+
+        let forest = parser.parse(&toks("x+x")).unwrap();
+        assert!(!forest.is_ambiguous());
+        assert_eq!(forest.trees(10)[0], DerivTree::NonTerm {
+            lhs: "E".to_string(),
+            alt: 0,
+            children: vec![
+                DerivTree::NonTerm { lhs: "E".to_string(), alt: 1, children: vec![DerivTree::Term { tok: "x".to_string(), named: false }] },
+                DerivTree::Term { tok: "+".to_string(), named: false },
+                DerivTree::NonTerm { lhs: "E".to_string(), alt: 1, children: vec![DerivTree::Term { tok: "x".to_string(), named: false }] },
+            ],
+        });
+        assert!(parser.parse(&toks("x+")).is_none());
+    }
+
+    #[test]
+    fn test_parse_cycle() {
+        // S: A derives S, so there are infinitely many parses of "a"
+        let cfg = Cfg::from_yacc("%%\nS: A | 'a';\nA: S;\n").unwrap();
+        let forest = cfg.earley().parse(&toks("a")).unwrap();
+        assert_eq!(forest.count_trees(), DerivCount::Infinite);
+        assert!(forest.is_ambiguous());
+        assert_eq!(forest.trees(10).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_nullable() {
+        let cfg = Cfg::from_yacc("%%\nS: A A 'x';\nA: 'a' | ;\n").unwrap();
+        let forest = cfg.earley().parse(&toks("ax")).unwrap();
+        // the 'a' comes from the first A or the second
+        assert_eq!(forest.count_trees(), DerivCount::Finite(2));
+        assert_eq!(cfg.earley().parse(&toks("x")).unwrap().count_trees(), DerivCount::Finite(1));
+    }
+}
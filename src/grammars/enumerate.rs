@@ -0,0 +1,335 @@
+//! Every sentence of a CFG up to a given length, and how many derivations
+//! there are of each length.
+//!
+//! Both are built up one length at a time from the results for shorter
+//! lengths. A non-terminal can depend on others at the same length (through
+//! unit alternatives, or alternatives whose other symbols are nullable), so
+//! each length is iterated to a fixed point. With a cycle of such
+//! dependencies, e.g. `A: A | 'a'`, there are infinitely many derivations.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::grammars::Cfg;
+use crate::grammars::transform::{IdxRules, Sym};
+
+/// A number of derivations.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DerivCount {
+    /// Saturating at `u128::MAX`
+    Finite(u128),
+    Infinite,
+}
+
+impl DerivCount {
+    pub(crate) fn add(self, other: DerivCount) -> DerivCount {
+        match (self, other) {
+            (DerivCount::Finite(a), DerivCount::Finite(b)) => DerivCount::Finite(a.saturating_add(b)),
+            _ => DerivCount::Infinite,
+        }
+    }
+
+    pub(crate) fn mul(self, other: DerivCount) -> DerivCount {
+        match (self, other) {
+            (DerivCount::Finite(0), _) | (_, DerivCount::Finite(0)) => DerivCount::Finite(0),
+            (DerivCount::Finite(a), DerivCount::Finite(b)) => DerivCount::Finite(a.saturating_mul(b)),
+            _ => DerivCount::Infinite,
+        }
+    }
+}
+
+impl fmt::Display for DerivCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DerivCount::Finite(n) => write!(f, "{}", n),
+            DerivCount::Infinite => write!(f, "infinite"),
+        }
+    }
+}
+
+/// The number of sentences of one length, and of their derivations.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LengthCounts {
+    pub len: usize,
+    /// Distinct sentences
+    pub sentences: usize,
+    pub derivations: DerivCount,
+}
+
+impl LengthCounts {
+    /// Are there more derivations than sentences, so that some sentence of
+    /// this length has two derivation trees?
+    pub fn is_ambiguous(&self) -> bool {
+        match self.derivations {
+            DerivCount::Finite(n) => n > self.sentences as u128,
+            DerivCount::Infinite => true,
+        }
+    }
+}
+
+/// The sentences of a CFG up to some length.
+#[derive(Debug, Clone)]
+pub struct Enumeration {
+    sentences: Vec<Vec<String>>,
+    counts: Vec<LengthCounts>,
+}
+
+impl Enumeration {
+    /// The sentences in shortlex order: shortest first, and in lexicographic
+    /// order of their terminals within each length.
+    pub fn sentences(&self) -> &[Vec<String>] {
+        &self.sentences
+    }
+
+    /// For each length from 0 up, the number of sentences and derivations.
+    pub fn counts(&self) -> &[LengthCounts] {
+        &self.counts
+    }
+
+    pub fn is_ambiguous(&self) -> bool {
+        self.counts.iter().any(|c| c.is_ambiguous())
+    }
+}
+
+/// For each way the symbols of `alt` can derive `len` terminals, the lengths
+/// derived by each symbol, given which lengths each rule derives anything of.
+fn splits(alt: &[Sym], len: usize, derives: &dyn Fn(usize, usize) -> bool) -> Vec<Vec<usize>> {
+    match alt.split_first() {
+        None if len == 0 => vec![vec![]],
+        None => vec![],
+        Some((Sym::Term(_), rest)) => {
+            if len == 0 {
+                return vec![];
+            }
+            splits(rest, len - 1, derives)
+                .into_iter()
+                .map(|split| std::iter::once(1).chain(split).collect())
+                .collect()
+        }
+        Some((Sym::NonTerm(None), _)) => vec![],
+        Some((Sym::NonTerm(Some(ridx)), rest)) => (0..=len)
+            .filter(|n| derives(*ridx, *n))
+            .flat_map(|n| splits(rest, len - n, derives)
+                .into_iter()
+                .map(move |split| std::iter::once(n).chain(split).collect()))
+            .collect(),
+    }
+}
+
+/// The sentences derived by each rule, by length.
+struct SentenceSets {
+    rules: IdxRules,
+    /// Indexed by rule, then length
+    sets: Vec<Vec<BTreeSet<Vec<String>>>>,
+}
+
+impl SentenceSets {
+    fn new(cfg: &Cfg, max_len: usize) -> Self {
+        let rules = IdxRules::new(cfg);
+        let mut sets = SentenceSets {
+            sets: vec![Vec::new(); rules.lhss.len()],
+            rules,
+        };
+        for len in 0..=max_len {
+            sets.add_len(len);
+        }
+
+        sets
+    }
+
+    fn add_len(&mut self, len: usize) {
+        for rule_sets in self.sets.iter_mut() {
+            rule_sets.push(BTreeSet::new());
+        }
+        loop {
+            let mut changed = false;
+            for ridx in 0..self.rules.lhss.len() {
+                for aidx in 0..self.rules.alts[ridx].len() {
+                    for sentence in self.alt_sentences(&self.rules.alts[ridx][aidx], len) {
+                        changed |= self.sets[ridx][len].insert(sentence);
+                    }
+                }
+            }
+            if !changed {
+                return;
+            }
+        }
+    }
+
+    fn alt_sentences(&self, alt: &[Sym], len: usize) -> Vec<Vec<String>> {
+        let derives = |ridx: usize, n: usize| !self.sets[ridx][n].is_empty();
+        let mut sentences = Vec::<Vec<String>>::new();
+        for split in splits(alt, len, &derives) {
+            let mut prefixes: Vec<Vec<String>> = vec![vec![]];
+            for (sym, n) in alt.iter().zip(split) {
+                prefixes = match sym {
+                    Sym::Term(tok) => prefixes
+                        .into_iter()
+                        .map(|mut prefix| {
+                            prefix.push(tok.to_string());
+                            prefix
+                        })
+                        .collect(),
+                    Sym::NonTerm(ridx) => {
+                        let set = &self.sets[ridx.expect("splits only use defined rules")][n];
+                        prefixes
+                            .iter()
+                            .flat_map(|prefix| set.iter().map(move |s| [prefix.as_slice(), s].concat()))
+                            .collect()
+                    }
+                };
+            }
+            sentences.extend(prefixes);
+        }
+
+        sentences
+    }
+}
+
+/// The number of derivations for each rule and length.
+fn derivation_counts(rules: &IdxRules, max_len: usize) -> Vec<Vec<DerivCount>> {
+    let n_rules = rules.lhss.len();
+    let mut counts = vec![Vec::<DerivCount>::new(); n_rules];
+    for len in 0..=max_len {
+        for rule_counts in counts.iter_mut() {
+            rule_counts.push(DerivCount::Finite(0));
+        }
+        // Without a cycle, counts settle after a pass per rule; then any
+        // count still rising after as many passes again is infinite.
+        let mut prev = Vec::<DerivCount>::new();
+        for _ in 0..2 * n_rules + 2 {
+            prev = counts.iter().map(|c| c[len]).collect();
+            let next: Vec<DerivCount> = (0..n_rules)
+                .map(|ridx| rules.alts[ridx]
+                    .iter()
+                    .map(|alt| alt_count(alt, len, &counts))
+                    .fold(DerivCount::Finite(0), DerivCount::add))
+                .collect();
+            for (ridx, count) in next.into_iter().enumerate() {
+                counts[ridx][len] = count;
+            }
+        }
+        for (ridx, prev) in prev.into_iter().enumerate() {
+            if counts[ridx][len] != prev {
+                counts[ridx][len] = DerivCount::Infinite;
+            }
+        }
+        // and so is any count depending on one
+        for _ in 0..n_rules {
+            for ridx in 0..n_rules {
+                if counts[ridx][len] != DerivCount::Infinite {
+                    counts[ridx][len] = rules.alts[ridx]
+                        .iter()
+                        .map(|alt| alt_count(alt, len, &counts))
+                        .fold(DerivCount::Finite(0), DerivCount::add);
+                }
+            }
+        }
+    }
+
+    counts
+}
+
+/// The number of ways the symbols of `alt` derive `len` terminals.
+fn alt_count(alt: &[Sym], len: usize, counts: &[Vec<DerivCount>]) -> DerivCount {
+    // ways[m]: the ways the symbols from here on derive m terminals
+    let mut ways: Vec<DerivCount> = (0..=len).map(|m| DerivCount::Finite((m == 0) as u128)).collect();
+    for sym in alt.iter().rev() {
+        ways = (0..=len)
+            .map(|m| match sym {
+                Sym::Term(_) if m == 0 => DerivCount::Finite(0),
+                Sym::Term(_) => ways[m - 1],
+                Sym::NonTerm(None) => DerivCount::Finite(0),
+                Sym::NonTerm(Some(ridx)) => (0..=m)
+                    .map(|n| counts[*ridx][n].mul(ways[m - n]))
+                    .fold(DerivCount::Finite(0), DerivCount::add),
+            })
+            .collect();
+    }
+
+    ways[len]
+}
+
+impl Cfg {
+    /// Every sentence of at most `max_len` terminals, with the number of
+    /// sentences and derivations of each length. This takes time and space
+    /// in proportion to the number of sentences.
+    pub fn enumerate(&self, max_len: usize) -> Enumeration {
+        if self.rules.is_empty() {
+            return Enumeration { sentences: vec![], counts: vec![] };
+        }
+        let sets = SentenceSets::new(self, max_len);
+        let derivations = derivation_counts(&sets.rules, max_len);
+        let counts = (0..=max_len)
+            .map(|len| LengthCounts {
+                len,
+                sentences: sets.sets[0][len].len(),
+                derivations: derivations[0][len],
+            })
+            .collect();
+        let sentences = sets.sets
+            .into_iter()
+            .next()
+            .expect("there is a start rule")
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Enumeration { sentences, counts }
+    }
+
+    /// For each length from 0 to `max_len`, the number of derivations of a
+    /// sentence of that length. Unlike `enumerate`, this is cheap for large
+    /// lengths: it takes time polynomial in `max_len`.
+    pub fn count_derivations(&self, max_len: usize) -> Vec<DerivCount> {
+        if self.rules.is_empty() {
+            return vec![DerivCount::Finite(0); max_len + 1];
+        }
+        derivation_counts(&IdxRules::new(self), max_len)
+            .into_iter()
+            .next()
+            .expect("there is a start rule")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(sentences: &[Vec<String>]) -> Vec<String> {
+        sentences.iter().map(|s| s.concat()).collect()
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S 'b' | A;\nA: 'c' A | ;\n").unwrap();
+        let en = cfg.enumerate(4);
+        assert_eq!(words(en.sentences()), vec!["", "c", "ab", "cc", "acb", "ccc", "aabb", "accb", "cccc"]);
+        assert_eq!(en.counts().iter().map(|c| c.sentences).collect::<Vec<_>>(), vec![1, 1, 2, 2, 3]);
+        assert!(!en.is_ambiguous());
+        assert_eq!(cfg.count_derivations(4), [1, 1, 2, 2, 3].iter().map(|n| DerivCount::Finite(*n)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_enumerate_ambiguous() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' E | 'x';\n").unwrap();
+        let en = cfg.enumerate(5);
+        assert_eq!(words(en.sentences()), vec!["x", "x+x", "x+x+x"]);
+        assert_eq!(en.counts()[5], LengthCounts { len: 5, sentences: 1, derivations: DerivCount::Finite(2) });
+        assert!(!en.counts()[3].is_ambiguous());
+        assert!(en.counts()[5].is_ambiguous());
+        // the Catalan numbers
+        let counts = cfg.count_derivations(19);
+        assert_eq!(counts[19], DerivCount::Finite(4862));
+    }
+
+    #[test]
+    fn test_count_infinite_derivations() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'b' | 'c';\nA: B | 'a';\nB: A | ;\n").unwrap();
+        let counts = cfg.count_derivations(2);
+        assert_eq!(counts, vec![DerivCount::Finite(0), DerivCount::Infinite, DerivCount::Infinite]);
+        let en = cfg.enumerate(2);
+        assert_eq!(words(en.sentences()), vec!["b", "c", "ab"]);
+        assert!(en.is_ambiguous());
+    }
+}
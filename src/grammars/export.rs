@@ -0,0 +1,893 @@
+//! Export of a CFG to the grammar notations of other tools.
+//!
+//! Each target has its own rules for names and for quoting terminals: names
+//! are sanitised (and kept distinct, and clear of the target's reserved
+//! words), and terminals quoted so that they stand for exactly their
+//! characters.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::grammars::{Cfg, LexSymbol, RuleAlt};
+
+/// How a target spells names.
+struct NameRules {
+    /// A name valid in the target, given any name
+    sanitise: fn(&str) -> String,
+    /// Names the target reserves
+    reserved: &'static [&'static str],
+    /// Names differing only in case are the same name
+    fold_case: bool,
+}
+
+/// Names in a target, handed out in order of first use.
+struct TargetNames<'a> {
+    rules: &'a NameRules,
+    used: HashSet<String>,
+    names: HashMap<String, String>,
+}
+
+impl<'a> TargetNames<'a> {
+    fn new(rules: &'a NameRules) -> Self {
+        let mut names = Self {
+            rules,
+            used: HashSet::new(),
+            names: HashMap::new(),
+        };
+        for name in rules.reserved {
+            let key = names.key(name);
+            names.used.insert(key);
+        }
+        names
+    }
+
+    /// Each non-terminal's name.
+    fn nonterms(cfg: &Cfg, rules: &'a NameRules) -> Self {
+        let mut names = Self::new(rules);
+        let rhs_nts = cfg.rules
+            .iter()
+            .flat_map(|rule| rule.rhs.iter())
+            .flat_map(|alt| alt.lex_symbols.iter())
+            .filter_map(|sym| match sym {
+                LexSymbol::NonTerm(nt) => Some(&nt.tok),
+                LexSymbol::Term(_) => None,
+            });
+        for nt in cfg.rules.iter().map(|rule| &rule.lhs).chain(rhs_nts) {
+            names.add(nt);
+        }
+        names
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.rules.fold_case { name.to_lowercase() } else { name.to_string() }
+    }
+
+    /// A name, based on `base`, which is not yet in use.
+    fn fresh(&mut self, base: &str) -> String {
+        let base = (self.rules.sanitise)(base);
+        let mut name = base.clone();
+        let mut i = 0;
+        while self.used.contains(&self.key(&name)) {
+            i += 1;
+            name = format!("{}{}", base, i);
+        }
+        let key = self.key(&name);
+        self.used.insert(key);
+        name
+    }
+
+    fn add(&mut self, name: &str) {
+        if !self.names.contains_key(name) {
+            let target = self.fresh(name);
+            self.names.insert(name.to_string(), target);
+        }
+    }
+
+    fn get(&self, name: &str) -> &str {
+        &self.names[name]
+    }
+}
+
+/// `tok` as strings quoted with `'` or `"`, for notations with no escapes:
+/// as one string if possible, otherwise split where both quotes would be
+/// needed.
+fn quote_pieces(tok: &str) -> Vec<String> {
+    let mut pieces = Vec::<String>::new();
+    let mut piece = String::new();
+    for c in tok.chars() {
+        if (c == '\'' && piece.contains('"')) || (c == '"' && piece.contains('\'')) {
+            pieces.push(piece);
+            piece = String::new();
+        }
+        piece.push(c);
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    pieces
+        .into_iter()
+        .map(|piece| if piece.contains('\'') { format!("\"{}\"", piece) } else { format!("'{}'", piece) })
+        .collect()
+}
+
+/// The symbols of `alt` in a target, with terminals given by `term`.
+fn alt_syms(alt: &RuleAlt, names: &TargetNames, term: &dyn Fn(&str) -> Vec<String>) -> Vec<String> {
+    alt.lex_symbols
+        .iter()
+        .flat_map(|sym| match sym {
+            LexSymbol::Term(t) => term(&t.tok),
+            LexSymbol::NonTerm(nt) => vec![names.get(&nt.tok).to_string()],
+        })
+        .collect()
+}
+
+/// ISO 14977 meta-identifiers: a letter, then letters and digits.
+const ISO_EBNF_NAMES: NameRules = NameRules {
+    sanitise: |name| {
+        let name: String = name.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        match name.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() => name,
+            _ => format!("r{}", name),
+        }
+    },
+    reserved: &[],
+    fold_case: false,
+};
+
+/// W3C (XML) names: a letter or `_`, then letters, digits, `_`, `-` and `.`.
+const W3C_EBNF_NAMES: NameRules = NameRules {
+    sanitise: |name| {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        match name.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => name,
+            _ => format!("_{}", name),
+        }
+    },
+    reserved: &[],
+    fold_case: false,
+};
+
+/// RFC 5234 rule names: a letter, then letters, digits and `-`, ignoring
+/// case; the core rules are implicitly defined.
+const ABNF_NAMES: NameRules = NameRules {
+    sanitise: |name| {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        match name.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() => name,
+            _ => format!("r{}", name),
+        }
+    },
+    reserved: &["ALPHA", "BIT", "CHAR", "CR", "CRLF", "CTL", "DIGIT", "DQUOTE", "HEXDIG", "HTAB", "LF", "LWSP",
+                "OCTET", "SP", "VCHAR", "WSP"],
+    fold_case: true,
+};
+
+/// An ABNF terminal: a quoted string only if that is exact, as quoted
+/// strings are printable ASCII without `"`, and match letters in either
+/// case; otherwise the character codes.
+fn abnf_term(tok: &str) -> Vec<String> {
+    if tok.chars().all(|c| (' '..='~').contains(&c) && c != '"' && !c.is_ascii_alphabetic()) {
+        return vec![format!("\"{}\"", tok)];
+    }
+    let codes: Vec<String> = tok.chars().map(|c| format!("{:X}", c as u32)).collect();
+    vec![format!("%x{}", codes.join("."))]
+}
+
+/// A name starting with a lower case letter, then letters, digits and `_`.
+fn lower_ident(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => format!("{}{}", c.to_ascii_lowercase(), &name[1..]),
+        _ => format!("r{}", name),
+    }
+}
+
+/// An upper case name for a terminal: a word is named for itself (`'if'` is
+/// `IF`), anything else for its character codes (`'+'` is `T_2B`).
+fn token_name(tok: &str) -> String {
+    match tok.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() && tok.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            tok.to_ascii_uppercase()
+        }
+        _ => {
+            let codes: Vec<String> = tok.chars().map(|c| format!("{:X}", c as u32)).collect();
+            format!("T_{}", codes.join("_"))
+        }
+    }
+}
+
+/// ANTLR parser rule names: a lower case letter, then letters, digits and
+/// `_`, other than ANTLR's keywords.
+const ANTLR_RULE_NAMES: NameRules = NameRules {
+    sanitise: lower_ident,
+    reserved: &["catch", "finally", "fragment", "grammar", "import", "lexer", "locals", "mode", "options", "parser",
+                "returns", "throws", "tokens"],
+    fold_case: false,
+};
+
+/// ANTLR token names: upper case.
+const ANTLR_TOKEN_NAMES: NameRules = NameRules {
+    sanitise: token_name,
+    reserved: &["EOF", "WS"],
+    fold_case: false,
+};
+
+/// Whitespace, which the lexer skips, separates the tokens of a sentence.
+const ANTLR_WS_RULE: &str = "WS : [ \\t\\r\\n]+ -> skip ;";
+
+/// Each terminal's name.
+fn token_names(cfg: &Cfg, rules: &'static NameRules) -> TargetNames<'static> {
+    let mut names = TargetNames::new(rules);
+    for tok in cfg.terminals() {
+        names.add(&tok);
+    }
+    names
+}
+
+/// Menhir non-terminals: lower case, and not OCaml keywords (as they name
+/// OCaml functions) or `error`.
+const MENHIR_RULE_NAMES: NameRules = NameRules {
+    sanitise: lower_ident,
+    reserved: &["and", "as", "assert", "begin", "class", "constraint", "do", "done", "downto", "else", "end",
+                "error", "exception", "external", "false", "for", "fun", "function", "functor", "if", "in",
+                "include", "inherit", "initializer", "lazy", "let", "match", "method", "module", "mutable", "new",
+                "nonrec", "object", "of", "open", "or", "private", "rec", "sig", "struct", "then", "to", "true",
+                "try", "type", "val", "virtual", "when", "while", "with"],
+    fold_case: false,
+};
+
+/// Menhir tokens: upper case, and `EOF` ends the input.
+const MENHIR_TOKEN_NAMES: NameRules = NameRules {
+    sanitise: token_name,
+    reserved: &["EOF"],
+    fold_case: false,
+};
+
+/// lemon non-terminals: lower case, other than `error`.
+const LEMON_RULE_NAMES: NameRules = NameRules {
+    sanitise: lower_ident,
+    reserved: &["error"],
+    fold_case: false,
+};
+
+/// lemon terminals: upper case.
+const LEMON_TOKEN_NAMES: NameRules = NameRules {
+    sanitise: token_name,
+    reserved: &[],
+    fold_case: false,
+};
+
+/// yacc names: letters, digits, `_` and `.`, not starting with a digit, and
+/// other than `error`. Terminals and non-terminals share these names.
+const BYACC_NAMES: NameRules = NameRules {
+    sanitise: |name| {
+        let name: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '_' })
+            .collect();
+        match name.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => name,
+            _ => format!("r{}", name),
+        }
+    },
+    reserved: &["error"],
+    fold_case: false,
+};
+
+/// Each terminal of `cfg` in byacc: a named token keeps its name, and other
+/// terminals are character literals if they are a single printable
+/// character, otherwise named tokens.
+fn byacc_tokens(cfg: &Cfg, names: &mut TargetNames) -> HashMap<String, String> {
+    let named = cfg.named_tokens();
+    let mut tokens = HashMap::<String, String>::new();
+    for tok in cfg.terminals() {
+        if named.contains(&tok) {
+            let target = names.fresh(&tok);
+            tokens.insert(tok, target);
+            continue;
+        }
+        let mut chars = tok.chars();
+        let literal = match (chars.next(), chars.next()) {
+            (Some(c), None) => (' '..='~').contains(&c) && c != '\'' && c != '\\',
+            _ => false,
+        };
+        let target = if literal { format!("'{}'", tok) } else { names.fresh(&token_name(&tok)) };
+        tokens.insert(tok, target);
+    }
+    tokens
+}
+
+/// The Rust keywords, which pest and the peg crate turn rule names into.
+const RUST_KEYWORDS: [&str; 51] = ["Self", "abstract", "as", "async", "await", "become", "box", "break", "const",
+    "continue", "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self",
+    "static", "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield"];
+
+/// A Rust identifier: letters, digits and `_`, not starting with a digit,
+/// and not a keyword.
+fn rust_ident(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        _ if RUST_KEYWORDS.contains(&name.as_str()) => format!("{}_", name),
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => name,
+        _ => format!("r{}", name),
+    }
+}
+
+/// pest rule names: Rust identifiers, other than pest's built in rules.
+const PEST_NAMES: NameRules = NameRules {
+    sanitise: rust_ident,
+    reserved: &["ANY", "ASCII", "ASCII_ALPHA", "ASCII_ALPHANUMERIC", "ASCII_ALPHA_LOWER", "ASCII_ALPHA_UPPER",
+                "ASCII_BIN_DIGIT", "ASCII_DIGIT", "ASCII_HEX_DIGIT", "ASCII_NONZERO_DIGIT", "ASCII_OCT_DIGIT",
+                "COMMENT", "DROP", "EOI", "NEWLINE", "PEEK", "PEEK_ALL", "POP", "POP_ALL", "PUSH", "SOI",
+                "WHITESPACE"],
+    fold_case: false,
+};
+
+/// peg crate rule names: Rust identifiers, other than the whitespace rule.
+const PEG_NAMES: NameRules = NameRules {
+    sanitise: rust_ident,
+    reserved: &["ws"],
+    fold_case: false,
+};
+
+/// Longest sentences on which PEG exports are compared with the CFG
+const PEG_CHECK_LEN: usize = 4;
+
+/// The CFG to export as a PEG, with no left recursion (on which a PEG would
+/// loop), and comment lines noting how reading it as a PEG changes it.
+fn peg_cfg(cfg: &Cfg) -> (Cfg, String) {
+    let mut notes = String::new();
+    let cfg = if cfg.is_left_recursive() {
+        notes += "// left recursion has been removed, as PEGs do not support it\n";
+        cfg.remove_left_recursion()
+    } else {
+        cfg.clone()
+    };
+    for line in cfg.peg_analysis(PEG_CHECK_LEN).to_string().lines() {
+        notes += &format!("// {}\n", line);
+    }
+    (cfg, notes)
+}
+
+fn antlr_str(tok: &str) -> String {
+    let mut s = String::from("'");
+    for c in tok.chars() {
+        match c {
+            '\'' => s.push_str("\\'"),
+            '\\' => s.push_str("\\\\"),
+            c if c.is_control() => s.push_str(&format!("\\u{:04X}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('\'');
+    s
+}
+
+/// `tok` as a regular expression matching exactly its characters.
+fn regex_str(tok: &str) -> String {
+    let mut s = String::new();
+    for c in tok.chars() {
+        match c {
+            c if "\\.+*?()|[]{}^$#&-~".contains(c) => {
+                s.push('\\');
+                s.push(c);
+            }
+            c if c.is_whitespace() || c.is_control() => s.push_str(&format!("\\x{{{:X}}}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s
+}
+
+/// `tok` as a flex string.
+fn flex_str(tok: &str) -> String {
+    let mut s = String::from("\"");
+    for c in tok.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            c if c.is_control() => s.push_str(&format!("\\x{:02X}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+impl Cfg {
+    /// The CFG in ISO 14977 EBNF: `S = 'a', B | ;`.
+    pub fn as_ebnf(&self) -> String {
+        let names = TargetNames::nonterms(self, &ISO_EBNF_NAMES);
+        let mut s = String::new();
+        for rule in &self.rules {
+            let alts: Vec<String> = rule.rhs
+                .iter()
+                .map(|alt| alt_syms(alt, &names, &quote_pieces).join(", "))
+                .collect();
+            s += &format!("{} = {};\n", names.get(&rule.lhs), alts.join(" | "));
+        }
+        s
+    }
+
+    /// The CFG in the W3C EBNF notation of the XML specification, as read
+    /// by railroad diagram generators: `S ::= ( 'a' B )?`. That has no
+    /// empty sequence, so rules with an empty alternative are made optional.
+    pub fn as_w3c_ebnf(&self) -> String {
+        let names = TargetNames::nonterms(self, &W3C_EBNF_NAMES);
+        let mut s = String::new();
+        for rule in &self.rules {
+            let alts: Vec<String> = rule.rhs
+                .iter()
+                .filter(|alt| !alt.lex_symbols.is_empty())
+                .map(|alt| alt_syms(alt, &names, &quote_pieces).join(" "))
+                .collect();
+            let rhs = if alts.is_empty() {
+                "/* empty */".to_string()
+            } else if alts.len() < rule.rhs.len() {
+                format!("( {} )?", alts.join(" | "))
+            } else {
+                alts.join(" | ")
+            };
+            s += &format!("{} ::= {}\n", names.get(&rule.lhs), rhs);
+        }
+        s
+    }
+
+    /// The CFG in RFC 5234 ABNF, with its CRLF line endings:
+    /// `S = %x61 B / ""`.
+    pub fn as_abnf(&self) -> String {
+        let names = TargetNames::nonterms(self, &ABNF_NAMES);
+        let mut s = String::new();
+        for rule in &self.rules {
+            let alts: Vec<String> = rule.rhs
+                .iter()
+                .map(|alt| match alt_syms(alt, &names, &abnf_term) {
+                    syms if syms.is_empty() => "\"\"".to_string(),
+                    syms => syms.join(" "),
+                })
+                .collect();
+            s += &format!("{} = {}\r\n", names.get(&rule.lhs), alts.join(" / "));
+        }
+        s
+    }
+
+    /// The CFG as the ANTLR 4 parser grammar `{name}Parser`, using the tokens
+    /// of the lexer grammar `{name}Lexer` from `as_antlr4_lexer`. A start rule
+    /// `sentence` is added, matching the whole input. ANTLR only handles
+    /// direct left recursion: any other is removed, and this noted in a
+    /// comment.
+    pub fn as_antlr4(&self, name: &str) -> String {
+        let tokens = token_names(self, &ANTLR_TOKEN_NAMES);
+        let mut s = format!("parser grammar {}Parser;\n\noptions {{ tokenVocab={}Lexer; }}\n\n", name, name);
+        let cfg = if self.has_indirect_left_recursion() {
+            s += "// indirect left recursion has been removed, as ANTLR does not support it\n\n";
+            self.remove_left_recursion()
+        } else {
+            self.clone()
+        };
+        let mut names = TargetNames::nonterms(&cfg, &ANTLR_RULE_NAMES);
+        if let Some(start) = cfg.start_rule() {
+            let sentence = names.fresh("sentence");
+            s += &format!("{}\n    : {} EOF\n    ;\n", sentence, names.get(&start.lhs));
+        }
+        for rule in &cfg.rules {
+            s += &format!("\n{}\n", names.get(&rule.lhs));
+            for (i, alt) in rule.rhs.iter().enumerate() {
+                let syms = alt_syms(alt, &names, &|tok| vec![tokens.get(tok).to_string()]);
+                let line = format!("    {} {}", if i == 0 { ":" } else { "|" }, syms.join(" "));
+                s += &format!("{}\n", line.trim_end());
+            }
+            s += "    ;\n";
+        }
+        s
+    }
+
+    /// The CFG as a Menhir grammar, with a start rule `sentence` matching
+    /// the whole input up to an `EOF` token. Every production has the unit
+    /// semantic action.
+    pub fn as_menhir(&self) -> String {
+        let start = self.start_rule()
+            .expect("Cfg is missing a start rule!");
+        let tokens = token_names(self, &MENHIR_TOKEN_NAMES);
+        let mut names = TargetNames::nonterms(self, &MENHIR_RULE_NAMES);
+        let mut s = String::new();
+        for tok in self.terminals() {
+            s += &format!("%token {}\n", tokens.get(&tok));
+        }
+        s += "%token EOF\n";
+        let sentence = names.fresh("sentence");
+        s += &format!("%start <unit> {}\n\n%%\n\n{}:\n  | {} EOF {{ () }}\n", sentence, sentence, names.get(&start.lhs));
+        for rule in &self.rules {
+            s += &format!("\n{}:\n", names.get(&rule.lhs));
+            for alt in &rule.rhs {
+                let mut syms = alt_syms(alt, &names, &|tok| vec![tokens.get(tok).to_string()]);
+                syms.push("{ () }".to_string());
+                s += &format!("  | {}\n", syms.join(" "));
+            }
+        }
+        s
+    }
+
+    /// The CFG as a lemon grammar. lemon does not allow the start symbol on
+    /// a right-hand side, so a start rule `sentence` is added.
+    pub fn as_lemon(&self) -> String {
+        let start = self.start_rule()
+            .expect("Cfg is missing a start rule!");
+        let tokens = token_names(self, &LEMON_TOKEN_NAMES);
+        let mut names = TargetNames::nonterms(self, &LEMON_RULE_NAMES);
+        let sentence = names.fresh("sentence");
+        let mut s = format!("%start_symbol {}\n\n{} ::= {}.\n", sentence, sentence, names.get(&start.lhs));
+        for rule in &self.rules {
+            for alt in &rule.rhs {
+                let syms = alt_syms(alt, &names, &|tok| vec![tokens.get(tok).to_string()]);
+                s += &format!("{} ::= {}.\n", names.get(&rule.lhs), syms.join(" "));
+            }
+        }
+        s
+    }
+
+    /// The CFG as a Berkeley yacc grammar. byacc's character literals hold a
+    /// single character, so other terminals are declared as named tokens.
+    pub fn as_byacc(&self) -> String {
+        let start = self.start_rule()
+            .expect("Cfg is missing a start rule!");
+        let mut names = TargetNames::nonterms(self, &BYACC_NAMES);
+        let tokens = byacc_tokens(self, &mut names);
+        let named: Vec<&str> = self.terminals()
+            .iter()
+            .map(|tok| tokens[tok].as_str())
+            .filter(|target| !target.starts_with('\''))
+            .collect();
+        let mut s = String::new();
+        if !named.is_empty() {
+            s += &format!("%token {}\n", named.join(" "));
+        }
+        s += &format!("%start {}\n\n%%\n\n", names.get(&start.lhs));
+        for rule in &self.rules {
+            let alts: Vec<String> = rule.rhs
+                .iter()
+                .map(|alt| match alt_syms(alt, &names, &|tok| vec![tokens[tok].to_string()]) {
+                    syms if syms.is_empty() => "/* empty */".to_string(),
+                    syms => syms.join(" "),
+                })
+                .collect();
+            s += &format!("{}: {}\n;\n", names.get(&rule.lhs), alts.join(" | "));
+        }
+        s += "\n%%";
+        s
+    }
+
+    /// The CFG as a pest grammar, with the alternatives of each rule as
+    /// ordered choices, a start rule `sentence` matching the whole input,
+    /// and whitespace between terminals skipped. Comments note where the
+    /// ordered choices change the language (see `peg_analysis`).
+    pub fn as_pest(&self) -> String {
+        let (cfg, mut s) = peg_cfg(self);
+        let start = cfg.start_rule()
+            .expect("Cfg is missing a start rule!");
+        let mut names = TargetNames::nonterms(&cfg, &PEST_NAMES);
+        let sentence = names.fresh("sentence");
+        s += &format!("\nWHITESPACE = _{{ \" \" | \"\\t\" | \"\\r\" | \"\\n\" }}\n\n\
+                       {} = {{ SOI ~ {} ~ EOI }}\n", sentence, names.get(&start.lhs));
+        for rule in &cfg.rules {
+            let alts: Vec<String> = rule.rhs
+                .iter()
+                .map(|alt| match alt_syms(alt, &names, &|tok| vec![format!("{:?}", tok)]) {
+                    syms if syms.is_empty() => "\"\"".to_string(),
+                    syms => syms.join(" ~ "),
+                })
+                .collect();
+            s += &format!("{} = {{ {} }}\n", names.get(&rule.lhs), alts.join(" | "));
+        }
+        s
+    }
+
+    /// The CFG as a grammar `name` for the peg crate's `parser!` macro, with
+    /// the alternatives of each rule as ordered choices, a public start rule
+    /// `sentence` matching the whole input, and whitespace before terminals
+    /// skipped. Comments note where the ordered choices change the language
+    /// (see `peg_analysis`).
+    pub fn as_peg(&self, name: &str) -> String {
+        let (cfg, mut s) = peg_cfg(self);
+        let start = cfg.start_rule()
+            .expect("Cfg is missing a start rule!");
+        let mut names = TargetNames::nonterms(&cfg, &PEG_NAMES);
+        let sentence = names.fresh("sentence");
+        s += &format!("\npeg::parser! {{\n    pub grammar {}() for str {{\n        \
+                       pub rule {}() = {}() ws() ![_]\n", name, sentence, names.get(&start.lhs));
+        for rule in &cfg.rules {
+            let alts: Vec<String> = rule.rhs
+                .iter()
+                .map(|alt| {
+                    let syms: Vec<String> = alt.lex_symbols
+                        .iter()
+                        .map(|sym| match sym {
+                            LexSymbol::Term(t) => format!("ws() {:?}", t.tok),
+                            LexSymbol::NonTerm(nt) => format!("{}()", names.get(&nt.tok)),
+                        })
+                        .collect();
+                    if syms.is_empty() { "\"\"".to_string() } else { syms.join(" ") }
+                })
+                .collect();
+            s += &format!("        rule {}() = {}\n", names.get(&rule.lhs), alts.join(" / "));
+        }
+        s += "        rule ws() = quiet!{[' ' | '\\t' | '\\r' | '\\n']*}\n    }\n}\n";
+        s
+    }
+
+    /// The ANTLR 4 lexer grammar `{name}Lexer`, with a token for each
+    /// terminal of the CFG, for the parser grammar from `as_antlr4`.
+    pub fn as_antlr4_lexer(&self, name: &str) -> String {
+        let tokens = token_names(self, &ANTLR_TOKEN_NAMES);
+        let mut s = format!("lexer grammar {}Lexer;\n\n", name);
+        for tok in self.terminals() {
+            s += &format!("{} : {} ;\n", tokens.get(&tok), antlr_str(&tok));
+        }
+        s += &format!("\n{}\n", ANTLR_WS_RULE);
+        s
+    }
+
+    /// An lrlex lexer for the grammar from `as_lrpar`, with a rule for each
+    /// terminal named as the grammar names it, and whitespace skipped. As in
+    /// the CFG's sentences, a named token is spelt as its name.
+    pub fn as_lrlex(&self) -> String {
+        let mut s = String::from("%%\n");
+        for tok in self.terminals() {
+            let name = if tok.contains('"') { format!("'{}'", tok) } else { format!("\"{}\"", tok) };
+            s += &format!("{} {}\n", regex_str(&tok), name);
+        }
+        s += "[\\t\\n\\r ]+ ;\n";
+        s
+    }
+
+    /// A flex lexer for the grammar from `as_byacc` (or `as_yacc`, when each
+    /// terminal is a single printable character), returning each terminal's
+    /// character or token code, and skipping whitespace.
+    pub fn as_flex(&self) -> String {
+        let mut names = TargetNames::nonterms(self, &BYACC_NAMES);
+        let tokens = byacc_tokens(self, &mut names);
+        let mut s = String::new();
+        if tokens.values().any(|target| !target.starts_with('\'')) {
+            s += "%{\n#include \"y.tab.h\"\n%}\n\n";
+        }
+        s += "%option noyywrap\n\n%%\n\n";
+        for tok in self.terminals() {
+            s += &format!("{} {{ return {}; }}\n", flex_str(&tok), tokens[&tok]);
+        }
+        s += "[ \\t\\r\\n]+ ;\n. { return yytext[0]; }\n\n%%\n";
+        s
+    }
+
+    /// The terminals of the CFG, one per line, as they are spelt in its
+    /// sentences.
+    pub fn as_token_list(&self) -> String {
+        self.terminals().iter().map(|tok| format!("{}\n", tok)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammars::{CfgRule, NonTermSymbol, TermSymbol};
+
+    use super::*;
+
+    /// Symbols starting with `'` are terminals.
+    fn cfg(rules: &[(&str, &[&[&str]])]) -> Cfg {
+        let rules = rules
+            .iter()
+            .map(|(lhs, alts)| {
+                let alts = alts
+                    .iter()
+                    .map(|alt| RuleAlt::new(alt
+                        .iter()
+                        .map(|sym| match sym.strip_prefix('\'') {
+                            Some(tok) => LexSymbol::Term(TermSymbol::new(tok.to_string())),
+                            None => LexSymbol::NonTerm(NonTermSymbol::new(sym.to_string())),
+                        })
+                        .collect()))
+                    .collect();
+                CfgRule::new(lhs.to_string(), alts)
+            })
+            .collect();
+        Cfg::new(rules)
+    }
+
+    fn test_cfg() -> Cfg {
+        cfg(&[
+            ("root", &[&["'a", "my_rule", "''"], &[]]),
+            ("my_rule", &[&["'b\"", "'x'\"y", "my_rule"], &["ALPHA"]]),
+            ("ALPHA", &[&["'A"]]),
+        ])
+    }
+
+    #[test]
+    fn test_quote_pieces() {
+        assert_eq!(quote_pieces("ab"), vec!["'ab'"]);
+        assert_eq!(quote_pieces("a'b"), vec!["\"a'b\""]);
+        assert_eq!(quote_pieces("x'\"y\"'"), vec!["\"x'\"", "'\"y\"'", "\"'\""]);
+    }
+
+    #[test]
+    fn test_as_ebnf() {
+        assert_eq!(test_cfg().as_ebnf(),
+                   "root = 'a', myrule, \"'\" | ;\n\
+                    myrule = 'b\"', \"x'\", '\"y', myrule | ALPHA;\n\
+                    ALPHA = 'A';\n");
+        assert_eq!(test_cfg().as_w3c_ebnf(),
+                   "root ::= ( 'a' my_rule \"'\" )?\n\
+                    my_rule ::= 'b\"' \"x'\" '\"y' my_rule | ALPHA\n\
+                    ALPHA ::= 'A'\n");
+    }
+
+    #[test]
+    fn test_as_antlr4() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' T | T;\nT: 'a' | 'A' | '(' E ')' | ;\n").unwrap();
+        assert_eq!(cfg.as_antlr4("Expr"),
+                   "parser grammar ExprParser;\n\noptions { tokenVocab=ExprLexer; }\n\n\
+                    sentence\n    : e EOF\n    ;\n\n\
+                    e\n    : e T_2B t\n    | t\n    ;\n\n\
+                    t\n    : A\n    | A1\n    | T_28 e T_29\n    |\n    ;\n");
+        assert_eq!(cfg.as_antlr4_lexer("Expr"),
+                   "lexer grammar ExprLexer;\n\n\
+                    T_2B : '+' ;\nA : 'a' ;\nA1 : 'A' ;\nT_28 : '(' ;\nT_29 : ')' ;\n\n\
+                    WS : [ \\t\\r\\n]+ -> skip ;\n");
+
+        let cfg = Cfg::from_yacc("%%\nS: A 'a' | 'b';\nA: S 'c' | 'd';\n").unwrap();
+        let antlr = cfg.as_antlr4("S");
+        assert!(antlr.contains("// indirect left recursion has been removed"));
+        assert!(antlr.contains("sentence\n    : s EOF\n    ;\n"));
+        assert_eq!(antlr_str("'\\\u{7}"), "'\\'\\\\\\u0007'");
+    }
+
+    #[test]
+    fn test_hidden_left_recursion() {
+        // S is directly left-recursive, and also through the nullable S
+        let cfg = Cfg::from_yacc("%%\nS: 'c' S S | S S 'b' | ;\n").unwrap();
+        assert!(cfg.as_antlr4("S").contains("// indirect left recursion has been removed"));
+        let (peg, notes) = peg_cfg(&cfg);
+        assert!(notes.starts_with("// left recursion has been removed"));
+        assert!(!peg.is_left_recursive());
+    }
+
+    fn lr_test_cfg() -> Cfg {
+        cfg(&[
+            ("E", &[&["E", "'+", "T"], &["T"]]),
+            ("T", &[&["'a"], &["'if"], &["''"], &["let"], &[]]),
+            ("let", &[&["'a"]]),
+        ])
+    }
+
+    #[test]
+    fn test_as_menhir() {
+        assert_eq!(lr_test_cfg().as_menhir(),
+                   "%token T_2B\n%token A\n%token IF\n%token T_27\n%token EOF\n\
+                    %start <unit> sentence\n\n%%\n\n\
+                    sentence:\n  | e EOF { () }\n\n\
+                    e:\n  | e T_2B t { () }\n  | t { () }\n\n\
+                    t:\n  | A { () }\n  | IF { () }\n  | T_27 { () }\n  | let1 { () }\n  | { () }\n\n\
+                    let1:\n  | A { () }\n");
+    }
+
+    #[test]
+    fn test_as_lemon() {
+        assert_eq!(lr_test_cfg().as_lemon(),
+                   "%start_symbol sentence\n\n\
+                    sentence ::= e.\n\
+                    e ::= e T_2B t.\ne ::= t.\n\
+                    t ::= A.\nt ::= IF.\nt ::= T_27.\nt ::= let.\nt ::= .\n\
+                    let ::= A.\n");
+    }
+
+    #[test]
+    fn test_as_byacc() {
+        assert_eq!(lr_test_cfg().as_byacc(),
+                   "%token IF T_27\n%start E\n\n%%\n\n\
+                    E: E '+' T | T\n;\n\
+                    T: 'a' | IF | T_27 | let | /* empty */\n;\n\
+                    let: 'a'\n;\n\n%%");
+    }
+
+    #[test]
+    fn test_as_pest() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'c' | A;\nA: 'a' | 'a' '\"' | ;\n").unwrap();
+        assert_eq!(cfg.as_pest(),
+                   "// as a PEG, rejects 2 of its 6 sentences of up to 4 terminals, such as 'a' '\"'\n\
+                    // alternative 1 of A is shadowed by alternative 0: 'a' is a prefix of 'a' '\"'\n\n\
+                    WHITESPACE = _{ \" \" | \"\\t\" | \"\\r\" | \"\\n\" }\n\n\
+                    sentence = { SOI ~ S ~ EOI }\n\
+                    S = { A ~ \"c\" | A }\n\
+                    A = { \"a\" | \"a\" ~ \"\\\"\" | \"\" }\n");
+    }
+
+    #[test]
+    fn test_as_peg() {
+        assert_eq!(rust_ident("type"), "type_");
+        // left recursion is removed, leaving the shorter alternatives first
+        let cfg = Cfg::from_yacc("%%\nE: E '+' 'x' | 'x';\n").unwrap();
+        assert_eq!(cfg.as_peg("expr"),
+                   "// left recursion has been removed, as PEGs do not support it\n\
+                    // as a PEG, rejects 1 of its 2 sentences of up to 4 terminals, such as 'x' '+' 'x'\n\
+                    // alternative 1 of E is shadowed by alternative 0: 'x' is a prefix of 'x' '+' 'x'\n\
+                    // alternative 1 of E_rest is shadowed by alternative 0: '+' 'x' is a prefix of '+' 'x' '+' 'x'\n\n\
+                    peg::parser! {\n    pub grammar expr() for str {\n        \
+                    pub rule sentence() = E() ws() ![_]\n        \
+                    rule E() = ws() \"x\" / ws() \"x\" E_rest()\n        \
+                    rule E_rest() = ws() \"+\" ws() \"x\" / ws() \"+\" ws() \"x\" E_rest()\n        \
+                    rule ws() = quiet!{[' ' | '\\t' | '\\r' | '\\n']*}\n    }\n}\n");
+    }
+
+    #[test]
+    fn test_as_abnf() {
+        assert_eq!(test_cfg().as_abnf(),
+                   "root = %x61 my-rule \"'\" / \"\"\r\n\
+                    my-rule = %x62.22 %x78.27.22.79 my-rule / ALPHA1\r\n\
+                    ALPHA1 = %x41\r\n");
+        // names differing only in case are the same name
+        let cfg = cfg(&[("S", &[&["s"]]), ("s", &[&["'+"]])]);
+        assert_eq!(cfg.as_abnf(), "S = s1\r\ns1 = \"+\"\r\n");
+    }
+
+    #[test]
+    fn test_as_lexers() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' T | T;\nT: '(' E ')' | 'id' | '\"';\n").unwrap();
+        assert_eq!(cfg.as_lrlex(),
+                   "%%\n\\+ \"+\"\n\\( \"(\"\n\\) \")\"\nid \"id\"\n\" '\"'\n[\\t\\n\\r ]+ ;\n");
+        assert_eq!(cfg.as_flex(),
+                   "%{\n#include \"y.tab.h\"\n%}\n\n%option noyywrap\n\n%%\n\n\
+                    \"+\" { return '+'; }\n\"(\" { return '('; }\n\")\" { return ')'; }\n\
+                    \"id\" { return ID; }\n\"\\\"\" { return '\"'; }\n\
+                    [ \\t\\r\\n]+ ;\n. { return yytext[0]; }\n\n%%\n");
+        assert_eq!(cfg.as_token_list(), "+\n(\n)\nid\n\"\n");
+        assert_eq!(regex_str("a.b c"), "a\\.b\\x{20}c");
+    }
+
+    #[test]
+    fn test_named_tokens() {
+        let cfg = Cfg::from_yacc("%token ID IF\n%%\nS: IF ID '=' ID | ID;\n").unwrap();
+        assert_eq!(cfg.as_byacc(), "%token IF ID\n%start S\n\n%%\n\nS: IF ID '=' ID | ID\n;\n\n%%");
+        assert_eq!(cfg.as_lrlex(), "%%\nIF \"IF\"\nID \"ID\"\n= \"=\"\n[\\t\\n\\r ]+ ;\n");
+        assert!(cfg.as_flex().contains("\"IF\" { return IF; }\n\"ID\" { return ID; }\n\"=\" { return '='; }\n"));
+        assert!(cfg.as_menhir().starts_with("%token IF\n%token ID\n"));
+    }
+
+    #[test]
+    fn test_lrlex_parses_sentences() {
+        use cfgrammar::yacc::{YaccGrammar, YaccKind, YaccOriginalActionKind};
+        use lrlex::{LexerDef, LRNonStreamingLexerDef};
+        use lrpar::RTParserBuilder;
+        use lrtable::{from_yacc, Minimiser};
+
+        use crate::grammars::coverage::Coverage;
+
+        let cfg = Cfg::from_yacc("%%\nE: E '+' T | T;\nT: '(' E ')' | 'id' | 'x*';\n").unwrap();
+        let grm = YaccGrammar::new(YaccKind::Original(YaccOriginalActionKind::NoAction), &cfg.as_hyacc()).unwrap();
+        let (_, stable) = from_yacc(&grm, Minimiser::Pager).unwrap();
+        let mut lexerdef = LRNonStreamingLexerDef::<u32>::from_str(&cfg.as_lrlex()).unwrap();
+        let ids = grm.tokens_map()
+            .into_iter()
+            .map(|(name, tidx)| (name, tidx.as_storaget()))
+            .collect();
+        assert_eq!(lexerdef.set_rule_ids(&ids), (None, None));
+
+        let parser = RTParserBuilder::new(&grm, &stable);
+        for sample in cfg.test_suite(Coverage::Pairs).samples {
+            for input in &[sample.sentence.join(" "), sample.sentence.concat()] {
+                assert!(parser.parse_noaction(&lexerdef.lexer(input)).is_empty(), "{} is rejected", input);
+            }
+        }
+        assert!(!parser.parse_noaction(&lexerdef.lexer("id id")).is_empty());
+    }
+}
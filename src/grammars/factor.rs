@@ -0,0 +1,122 @@
+//! Left factoring: alternatives of a rule sharing a common prefix, as in
+//! `A: x y B | x y C`, are merged into one alternative ending in a fresh
+//! non-terminal, `A: x y A_factor; A_factor: B | C`.
+
+use crate::grammars::{Cfg, CfgRule, LexSymbol, NonTermSymbol, RuleAlt};
+use crate::grammars::transform::{FreshNames, sym_rules, SymRule};
+
+/// The longest prefix shared by all of `alts`.
+fn common_prefix(alts: &[&Vec<LexSymbol>]) -> usize {
+    let first = alts[0];
+    (0..first.len())
+        .take_while(|i| alts.iter().all(|alt| alt.get(*i) == Some(&first[*i])))
+        .count()
+}
+
+/// Factor out the longest common prefix of the first group of alternatives
+/// of `rule` sharing a first symbol, returning the new rule.
+fn factor_rule(rule: &mut SymRule, names: &mut FreshNames) -> Option<SymRule> {
+    let (lhs, alts) = rule;
+    let (first_idx, first) = alts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, alt)| alt.first().map(|sym| (i, sym)))
+        .find(|(i, sym)| alts[i + 1..].iter().any(|alt| alt.first() == Some(sym)))?;
+    let first = first.clone();
+    let (group, rest): (Vec<Vec<LexSymbol>>, Vec<Vec<LexSymbol>>) = alts
+        .drain(..)
+        .partition(|alt| alt.first() == Some(&first));
+    let prefix_len = common_prefix(&group.iter().collect::<Vec<_>>());
+
+    let factor = names.fresh(&format!("{}_factor", lhs));
+    let mut factored = group[0][..prefix_len].to_vec();
+    factored.push(LexSymbol::NonTerm(NonTermSymbol::new(factor.to_string())));
+    let mut factor_alts = Vec::<Vec<LexSymbol>>::new();
+    for alt in group {
+        let suffix = alt[prefix_len..].to_vec();
+        if !factor_alts.contains(&suffix) {
+            factor_alts.push(suffix);
+        }
+    }
+
+    *alts = rest;
+    alts.insert(first_idx, factored);
+
+    Some((factor, factor_alts))
+}
+
+impl Cfg {
+    /// Left-factor every rule, repeating until no two alternatives of a
+    /// rule start with the same symbol.
+    pub fn left_factor(&self) -> Cfg {
+        let mut names = FreshNames::new(self);
+        let mut rules = sym_rules(self);
+        for (_, alts) in rules.iter_mut() {
+            let mut unique = Vec::<Vec<LexSymbol>>::new();
+            for alt in alts.drain(..) {
+                if !unique.contains(&alt) {
+                    unique.push(alt);
+                }
+            }
+            *alts = unique;
+        }
+
+        let mut i = 0;
+        while i < rules.len() {
+            match factor_rule(&mut rules[i], &mut names) {
+                Some(factor_rule) => {
+                    rules.push(factor_rule);
+                }
+                None => {
+                    i += 1;
+                }
+            }
+        }
+
+        Cfg::new(rules
+            .into_iter()
+            .map(|(lhs, alts)| CfgRule::new(lhs, alts.into_iter().map(RuleAlt::new).collect()))
+            .collect())
+    }
+
+    /// Do no two alternatives of a rule start with the same symbol?
+    pub fn is_left_factored(&self) -> bool {
+        self.rules.iter().all(|rule| {
+            rule.rhs.iter().enumerate().all(|(i, alt)| match alt.lex_symbols.first() {
+                Some(first) => !rule.rhs[i + 1..].iter().any(|other| other.lex_symbols.first() == Some(first)),
+                None => true,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_left_factor() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' 'b' B | 'c' | 'a' 'b' C | 'a' 'b';\nB: 'x';\nC: 'y';\n").unwrap();
+        assert!(!cfg.is_left_factored());
+        let factored = cfg.left_factor();
+        assert!(factored.is_left_factored());
+        assert_eq!(factored.to_string(),
+                   "S: 'a' 'b' S_factor | 'c'\n;\nB: 'x'\n;\nC: 'y'\n;\nS_factor: B | C | \n;\n");
+    }
+
+    #[test]
+    fn test_left_factor_repeated() {
+        // the new rule needs factoring too
+        let cfg = Cfg::from_yacc("%%\nS: 'a' 'b' | 'a' 'c' 'd' | 'a' 'c' 'e';\n").unwrap();
+        let factored = cfg.left_factor();
+        assert!(factored.is_left_factored());
+        assert_eq!(factored.to_string(),
+                   "S: 'a' S_factor\n;\nS_factor: 'b' | 'c' S_factor_factor\n;\nS_factor_factor: 'd' | 'e'\n;\n");
+    }
+
+    #[test]
+    fn test_left_factor_duplicates() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' | 'a' | 'b';\n").unwrap();
+        assert_eq!(cfg.left_factor().to_string(), "S: 'a' | 'b'\n;\n");
+    }
+}
@@ -1,9 +1,7 @@
-use std::{fs, path::Path, io};
+use std::{fs, path::Path, path::PathBuf, io};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use chrono::{
-    prelude::Local,
-    Timelike,
-};
 use prettytable::{row, cell};
 use prettytable::Table;
 use rand::{
@@ -14,7 +12,12 @@ use rand::{
 use rayon::prelude::*;
 
 use crate::grammars::{Cfg, CfgRule, LexSymbol, NonTermSymbol, RuleAlt, TermSymbol};
+use crate::grammars::counterexample::ConflictExample;
+use crate::grammars::coverage::Coverage;
 use crate::grammars::lr1_check;
+use crate::grammars::progress::{self, CfgGenProgress};
+use crate::grammars::stats::{CfgGenStats, CfgReject};
+use crate::grammars::workdir::WorkDir;
 
 const ASCII_LOWER: [char; 26] = [
     'a', 'b', 'c', 'd', 'e',
@@ -38,6 +41,11 @@ const MIN_ALTS: usize = 1;
 const MAX_ALTS: usize = 3;
 const MIN_SYMS_IN_ALT: usize = 0;
 const MAX_SYMS_IN_ALT: usize = 5;
+/// Attempts allowed in quota mode when no budget is given
+const DEFAULT_MAX_ATTEMPTS: usize = 100_000;
+/// Each round hands this many attempts per rayon thread to the pool;
+/// progress is reported between rounds.
+const BATCH_PER_THREAD: usize = 8;
 
 #[derive(Debug)]
 pub struct CfgGenError {
@@ -45,7 +53,7 @@ pub struct CfgGenError {
 }
 
 impl CfgGenError {
-    fn new(msg: String) -> Self {
+    pub(crate) fn new(msg: String) -> Self {
         Self {
             msg
         }
@@ -53,6 +61,7 @@ impl CfgGenError {
 }
 
 pub(crate) struct CfgLr1Result {
+    pub(crate) cfg: Cfg,
     pub(crate) bisonp: String,
     pub(crate) hyaccp: String,
     pub(crate) lrpar_lr1: bool,
@@ -61,10 +70,14 @@ pub(crate) struct CfgLr1Result {
     // pub(crate) bison_msg: String,
     pub(crate) hyacc_lr1: bool,
     // pub(crate) hyacc_msg: String,
+    pub(crate) hyacc_timeout: bool,
+    /// A counterexample for each LR(1) conflict, found on first use
+    conflict_examples: OnceLock<Vec<ConflictExample>>,
 }
 
 impl CfgLr1Result {
     pub(crate) fn new(
+        cfg: Cfg,
         bisonp: String,
         hyaccp: String,
         lrpar_lr1: bool,
@@ -73,8 +86,10 @@ impl CfgLr1Result {
         // bison_msg: String,
         hyacc_lr1: bool,
         // hyacc_msg: String,
+        hyacc_timeout: bool,
     ) -> Self {
         Self {
+            cfg,
             bisonp,
             hyaccp,
             lrpar_lr1,
@@ -83,35 +98,174 @@ impl CfgLr1Result {
             // bison_msg,
             hyacc_lr1,
             // hyacc_msg,
+            hyacc_timeout,
+            conflict_examples: OnceLock::new(),
+        }
+    }
+
+    /// A counterexample for each LR(1) conflict of the CFG. Finding them is
+    /// costly, so this is only done the first time they are asked for.
+    pub(crate) fn conflict_examples(&self) -> &[ConflictExample] {
+        self.conflict_examples.get_or_init(|| self.cfg.conflict_examples())
+    }
+
+    /// Accepted as LR(1) by both lrpar and Bison
+    pub(crate) fn is_lr1(&self) -> bool {
+        self.lrpar_lr1 && self.bison_lr1
+    }
+
+    /// Accepted by Hyacc, but not LR(1) according to Bison
+    pub(crate) fn is_lrk(&self) -> bool {
+        self.hyacc_lr1 && !self.bison_lr1
+    }
+}
+
+/// Targets for quota mode (see `CfgGen::gen_quota`). Generation stops once
+/// `lr1` LR(1) and `lrk` LR(k) grammars have been collected, or when one of
+/// the budgets runs out. With neither budget set, generation gives up after
+/// `DEFAULT_MAX_ATTEMPTS` attempts, as the quota may be out of reach.
+#[derive(Debug, Clone)]
+pub struct CfgGenQuota {
+    /// Number of LR(1) grammars to collect
+    pub lr1: usize,
+    /// Number of LR(k) (but not LR(1)) grammars to collect
+    pub lrk: usize,
+    /// Give up after this many attempts
+    pub max_attempts: Option<usize>,
+    /// Give up after this much time
+    pub max_time: Option<Duration>,
+}
+
+impl CfgGenQuota {
+    pub fn new(lr1: usize, lrk: usize) -> Self {
+        Self {
+            lr1,
+            lrk,
+            max_attempts: None,
+            max_time: None,
+        }
+    }
+
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    pub fn max_time(mut self, max_time: Duration) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    fn is_met(&self, lr1: usize, lrk: usize) -> bool {
+        lr1 >= self.lr1 && lrk >= self.lrk
+    }
+
+    /// The attempts allowed: `max_attempts`, or `DEFAULT_MAX_ATTEMPTS` if
+    /// there is no budget at all.
+    fn attempts_budget(&self) -> Option<usize> {
+        match (self.max_attempts, self.max_time) {
+            (None, None) => Some(DEFAULT_MAX_ATTEMPTS),
+            (max_attempts, _) => max_attempts,
+        }
+    }
+
+    /// Estimate the attempts left before the quota is met (or the attempts
+    /// budget runs out), going by the acceptance rates so far.
+    fn remaining_attempts(&self, stats: &CfgGenStats) -> Option<usize> {
+        let needed = |target: usize, found: usize| {
+            if found >= target {
+                Some(0)
+            } else if found == 0 {
+                None
+            } else {
+                Some(((target - found) * stats.attempted).div_ceil(found))
+            }
+        };
+        let remaining = match (needed(self.lr1, stats.lr1), needed(self.lrk, stats.lrk)) {
+            (Some(lr1), Some(lrk)) => Some(lr1.max(lrk)),
+            _ => None,
+        };
+        match self.attempts_budget() {
+            Some(max_attempts) => {
+                let budget = max_attempts.saturating_sub(stats.attempted);
+                Some(remaining.map_or(budget, |r| r.min(budget)))
+            }
+            None => remaining,
         }
     }
 }
 
+/// Where a generation run keeps its intermediate files, and the terminals
+/// its CFGs are made from.
+#[derive(Debug, Clone, Default)]
+pub struct CfgGenOptions {
+    /// Create the run's (uniquely named) working directory in here, rather
+    /// than in the system's temporary directory
+    pub work_dir: Option<PathBuf>,
+    /// Leave the working directory behind for debugging
+    pub keep_files: bool,
+    /// Remove the working directory and exit if the user hits Ctrl-C. This
+    /// installs a process-wide handler, so it is up to the application to
+    /// opt in
+    pub handle_ctrlc: bool,
+    /// Named tokens (such as `ID` and `NUM`) to use as terminals, rather than
+    /// the characters `a`-`z`; a CFG of n rules takes n of them
+    pub tokens: Vec<String>,
+}
+
+impl CfgGenOptions {
+    pub fn work_dir<P: Into<PathBuf>>(mut self, work_dir: P) -> Self {
+        self.work_dir = Some(work_dir.into());
+        self
+    }
+
+    pub fn keep_files(mut self, keep_files: bool) -> Self {
+        self.keep_files = keep_files;
+        self
+    }
+
+    pub fn handle_ctrlc(mut self, handle_ctrlc: bool) -> Self {
+        self.handle_ctrlc = handle_ctrlc;
+        self
+    }
+
+    pub fn tokens<S: Into<String>>(mut self, tokens: Vec<S>) -> Self {
+        self.tokens = tokens.into_iter().map(|tok| tok.into()).collect();
+        self
+    }
+}
+
 /// Stores the LR1 check result for CFGs
 pub(crate) struct CfgGenResult {
     /// LR1 checks for CFGs
     lr_checks: Vec<CfgLr1Result>,
     /// directory containing the CFGs
-    src_grammar_dir: String,
+    work_dir: WorkDir,
     /// Grammar size
     cfg_size: usize,
+    /// Per-stage acceptance, rejection reasons and rule shapes
+    stats: CfgGenStats,
 }
 
 impl CfgGenResult {
-    fn new(lr_checks: Vec<CfgLr1Result>, src_grammar_dir: String, cfg_size: usize) -> Self {
+    fn new(
+        lr_checks: Vec<CfgLr1Result>,
+        work_dir: WorkDir,
+        cfg_size: usize,
+        stats: CfgGenStats,
+    ) -> Self {
         Self {
             lr_checks,
-            src_grammar_dir,
+            work_dir,
             cfg_size,
+            stats,
         }
     }
 
     fn lr1_grammars(&self) -> Vec<&CfgLr1Result> {
         self.lr_checks
             .iter()
-            .filter(|res|
-                res.lrpar_lr1 && res.bison_lr1
-            )
+            .filter(|res| res.is_lr1())
             .collect()
     }
 
@@ -119,13 +273,13 @@ impl CfgGenResult {
     fn lrk_grammars(&self) -> Vec<&CfgLr1Result> {
         self.lr_checks
             .iter()
-            .filter(|res| res.hyacc_lr1 && !res.bison_lr1)
+            .filter(|res| res.is_lrk())
             .collect()
     }
 
     fn write_lr1(&self, out_dir: &str) -> Result<(), CfgGenError> {
         let lr1_cfgs = self.lr1_grammars();
-        println!("\n=> generated {}/{} lr(1) grammars", lr1_cfgs.len(), self.lr_checks.len());
+        info!("=> generated {}/{} lr(1) grammars", lr1_cfgs.len(), self.lr_checks.len());
 
         if !lr1_cfgs.is_empty() {
             let target_cfg_dir = format!("{}/lr1/{}", out_dir, self.cfg_size);
@@ -133,12 +287,11 @@ impl CfgGenResult {
                 .map_err(|_| CfgGenError::new(
                     format!("{} already directory exists!", target_cfg_dir)
                 ))?;
-            println!("=> copying lr(1) grammars to target dir: {}", target_cfg_dir);
-            println!("--- lr(1) grammars ---");
+            info!("=> copying lr(1) grammars to target dir: {}", target_cfg_dir);
             for res in lr1_cfgs {
                 let rnd_str = rand_alphanumeric(8);
                 let target_cfg_f = format!("{}/{}", target_cfg_dir, rnd_str);
-                println!("copying {} => {}", &res.bisonp, &target_cfg_f);
+                debug!("copying {} => {}", &res.bisonp, &target_cfg_f);
                 std::fs::copy(&res.bisonp, &target_cfg_f)
                     .map_err(|e| CfgGenError::new(
                         format!("Unable to copy cfg {} to {}, error:\n{}",
@@ -146,8 +299,9 @@ impl CfgGenResult {
                                 target_cfg_f,
                                 e.to_string())
                     ))?;
+                write_test_suite(&res.cfg, &target_cfg_f)?;
+                write_lexers(&res.cfg, &target_cfg_f)?;
             }
-            println!("---------\n\n");
         }
 
         Ok(())
@@ -155,7 +309,7 @@ impl CfgGenResult {
 
     fn write_lrk(&self, out_dir: &str) -> Result<(), CfgGenError> {
         let lrk_cfgs = self.lrk_grammars();
-        println!("\n=> generated {}/{} lr(k) grammars", lrk_cfgs.len(), self.lr_checks.len());
+        info!("=> generated {}/{} lr(k) grammars", lrk_cfgs.len(), self.lr_checks.len());
 
         if !lrk_cfgs.is_empty() {
             let target_cfg_dir = format!("{}/lr_k/{}", out_dir, self.cfg_size);
@@ -163,12 +317,11 @@ impl CfgGenResult {
                 CfgGenError::new(
                     format!("{} directory already exists!", target_cfg_dir)
                 ))?;
-            println!("=> copying lr(k) grammars to target dir: {}", target_cfg_dir);
-            println!("--- lr(k) grammars ---");
+            info!("=> copying lr(k) grammars to target dir: {}", target_cfg_dir);
             for res in lrk_cfgs {
                 let rnd_str = rand_alphanumeric(8);
                 let target_cfg_f = format!("{}/{}", target_cfg_dir, rnd_str);
-                println!("copying {} => {}", &res.hyaccp, &target_cfg_f);
+                debug!("copying {} => {}", &res.hyaccp, &target_cfg_f);
                 std::fs::copy(&res.hyaccp, &target_cfg_f)
                     .map_err(|e|
                         CfgGenError::new(format!(
@@ -176,29 +329,22 @@ impl CfgGenResult {
                             &res.hyaccp, &target_cfg_f, e.to_string()
                         )
                         ))?;
+                write_test_suite(&res.cfg, &target_cfg_f)?;
+                write_lexers(&res.cfg, &target_cfg_f)?;
+                write_conflicts(res, &target_cfg_f)?;
             }
-            println!("---------\n\n");
         }
 
         Ok(())
     }
 
-    pub(crate) fn write_results(&self, out_dir: &str) -> Result<(), CfgGenError> {
+    pub(crate) fn write_results(self, out_dir: &str) -> Result<(), CfgGenError> {
         self.write_lr1(out_dir)?;
         self.write_lrk(out_dir)?;
 
-        println!("=> cleaning up temporary directory: {}", self.src_grammar_dir);
-        let src_p = Path::new(&self.src_grammar_dir);
-        std::fs::remove_dir_all(&src_p)
-            .map_err(|e|
-                CfgGenError::new(
-                    format!("Unable to remove src grammar directory {}, Error:\n{}",
-                            &self.src_grammar_dir,
-                            e.to_string()
-                    )
-                ))?;
+        info!("=> generation statistics (size: {})\n{}", self.cfg_size, self.stats);
 
-        Ok(())
+        self.work_dir.close()
     }
 
     #[allow(dead_code)]
@@ -223,32 +369,43 @@ pub(crate) struct CfgGen {
 }
 
 impl CfgGen {
-    pub(crate) fn new(cfg_size: usize) -> Self {
-        // we also have a root non-term, so we need one less
+    /// A generator of CFGs with `cfg_size` rules, using `tokens` as their
+    /// terminals if any are given, otherwise characters.
+    pub(crate) fn new(cfg_size: usize, tokens: &[String]) -> Result<Self, CfgGenError> {
+        check_tokens(tokens, cfg_size)?;
+        // we also have a root non-term, so we need one less; none can share
+        // a token's name
         let non_terms: Vec<String> = ASCII_UPPER
-            .choose_multiple(&mut thread_rng(), cfg_size - 1)
-            .map(|c| c.to_string())
-            .collect();
-        let terms: Vec<String> = ASCII_LOWER
-            .choose_multiple(&mut thread_rng(), cfg_size)
+            .iter()
             .map(|c| c.to_string())
+            .filter(|nt| !tokens.contains(nt))
+            .collect::<Vec<String>>()
+            .choose_multiple(&mut thread_rng(), cfg_size - 1)
+            .cloned()
             .collect();
 
-        let mut lex_syms: Vec<LexSymbol> = terms
-            .iter()
-            .map(|t| LexSymbol::Term(TermSymbol::new(t.to_string())))
-            .collect();
+        let mut lex_syms: Vec<LexSymbol> = if tokens.is_empty() {
+            ASCII_LOWER
+                .choose_multiple(&mut thread_rng(), cfg_size)
+                .map(|t| LexSymbol::Term(TermSymbol::new(t.to_string())))
+                .collect()
+        } else {
+            tokens
+                .choose_multiple(&mut thread_rng(), cfg_size)
+                .map(|t| LexSymbol::Term(TermSymbol::named(t.to_string())))
+                .collect()
+        };
 
         for nt in non_terms.iter() {
             lex_syms.push(LexSymbol::NonTerm(NonTermSymbol::new(nt.to_string())));
         }
         lex_syms.shuffle(&mut thread_rng());
 
-        Self {
+        Ok(Self {
             cfg_size,
             non_terms,
             lex_syms,
-        }
+        })
     }
 
     fn get_lex_sym(&self, lex_syms: &[&LexSymbol], nt: &str) -> LexSymbol {
@@ -447,7 +604,9 @@ impl CfgGen {
         }
     }
 
-    fn generate(&self, cfg_no: usize, temp_dir: &str) -> Option<CfgLr1Result> {
+    /// Generate a CFG and, if it is reduced, run the LR tools on it.
+    /// The outcome is recorded in `stats`.
+    fn generate(&self, cfg_no: usize, temp_dir: &str, stats: &mut CfgGenStats) -> Option<CfgLr1Result> {
         let mut rules = Vec::<CfgRule>::new();
         let mut root_reach = Vec::<String>::new();
         {
@@ -466,43 +625,288 @@ impl CfgGen {
                 break;
             }
         }
+        stats.record_cfg(&rules);
         if !self.unreachable_non_terms(root_reach.as_slice()).is_empty() {
+            stats.record_reject(CfgReject::Unreachable);
             return None;
         }
 
         let cfg = Cfg::new(rules);
         if self.is_productive(&cfg) {
-            eprint!(".");
-            return Some(lr1_check::run_lr1_tools(cfg, cfg_no, temp_dir));
+            let res = lr1_check::run_lr1_tools(cfg, cfg_no, temp_dir);
+            stats.record_lr_checks(&res);
+            return Some(res);
         }
-        eprint!("X");
+        stats.record_reject(CfgReject::Unproductive);
         None
     }
 
-    /// Generate CFGs in parallel
-    pub(crate) fn gen_par(&self, n: usize) -> CfgGenResult {
-        let now = Local::now();
-        let grammar_dir = format!("/tmp/cfg_run_{}_{}_{}",
-                                  now.hour(),
-                                  now.minute(),
-                                  now.second()
-        );
-        fs::create_dir(&grammar_dir).expect("Unable to create a temporary directory");
-
-        let cfg_result: Vec<CfgLr1Result> = (0..n)
+    /// Run `generate` for CFGs numbered `from..to` in parallel, returning the
+    /// CFGs handed to the LR tools along with the generation statistics.
+    fn gen_range(&self, from: usize, to: usize, grammar_dir: &str)
+                 -> (Vec<CfgLr1Result>, CfgGenStats) {
+        (from..to)
             .into_par_iter()
-            .filter_map(|i| {
-                self.generate(i, &grammar_dir)
-            })
-            .collect();
+            .fold(
+                || (Vec::new(), CfgGenStats::default()),
+                |(mut results, mut stats), i| {
+                    if let Some(res) = self.generate(i, grammar_dir, &mut stats) {
+                        results.push(res);
+                    }
+                    (results, stats)
+                })
+            .reduce(
+                || (Vec::new(), CfgGenStats::default()),
+                |(mut results, mut stats), (mut other_results, other_stats)| {
+                    results.append(&mut other_results);
+                    stats.merge(&other_stats);
+                    (results, stats)
+                })
+    }
+
+    /// Generate `n` CFGs in parallel, a batch at a time, calling `progress`
+    /// after each batch.
+    pub(crate) fn gen_par(&self, n: usize, opts: &CfgGenOptions, progress: &mut dyn FnMut(&CfgGenProgress))
+                          -> Result<CfgGenResult, CfgGenError> {
+        let work_dir = WorkDir::new(opts)?;
+        let grammar_dir = work_dir.path_str();
+        let batch_size = rayon::current_num_threads() * BATCH_PER_THREAD;
+        let started = Instant::now();
+        let mut cfg_result = Vec::<CfgLr1Result>::new();
+        let mut stats = CfgGenStats::default();
+
+        while stats.attempted < n {
+            let from = stats.attempted;
+            let to = n.min(from + batch_size);
+            let (mut batch_result, batch_stats) = self.gen_range(from, to, grammar_dir);
+            cfg_result.append(&mut batch_result);
+            stats.merge(&batch_stats);
+
+            let elapsed = started.elapsed();
+            let eta = progress::eta(elapsed, stats.attempted, n - stats.attempted);
+            progress(&CfgGenProgress::new(self.cfg_size, &stats, Some(n), elapsed, eta));
+        }
+
+        Ok(CfgGenResult::new(cfg_result, work_dir, self.cfg_size, stats))
+    }
+
+    /// Keep generating CFGs in parallel, a batch at a time, until `quota` is
+    /// met or one of its budgets runs out. The last batch may overshoot the quota.
+    /// `progress` is called after each batch.
+    pub(crate) fn gen_quota(&self, quota: &CfgGenQuota, opts: &CfgGenOptions,
+                            progress: &mut dyn FnMut(&CfgGenProgress))
+                            -> Result<CfgGenResult, CfgGenError> {
+        let work_dir = WorkDir::new(opts)?;
+        let grammar_dir = work_dir.path_str();
+        let batch_size = rayon::current_num_threads() * BATCH_PER_THREAD;
+        let started = Instant::now();
+        let mut cfg_result = Vec::<CfgLr1Result>::new();
+        let mut stats = CfgGenStats::default();
+
+        while !quota.is_met(stats.lr1, stats.lrk) {
+            let from = stats.attempted;
+            let to = match quota.attempts_budget() {
+                Some(max_attempts) => {
+                    if from >= max_attempts {
+                        break;
+                    }
+                    max_attempts.min(from + batch_size)
+                }
+                None => from + batch_size,
+            };
+            if let Some(max_time) = quota.max_time {
+                if started.elapsed() >= max_time {
+                    break;
+                }
+            }
+
+            let (mut batch_result, batch_stats) = self.gen_range(from, to, grammar_dir);
+            cfg_result.append(&mut batch_result);
+            stats.merge(&batch_stats);
 
-        CfgGenResult::new(cfg_result, grammar_dir, self.cfg_size)
+            let elapsed = started.elapsed();
+            let mut eta = quota.remaining_attempts(&stats)
+                .and_then(|remaining| progress::eta(elapsed, stats.attempted, remaining));
+            if let Some(max_time) = quota.max_time {
+                let time_left = max_time.checked_sub(elapsed).unwrap_or_default();
+                eta = Some(eta.map_or(time_left, |eta| eta.min(time_left)));
+            }
+            progress(&CfgGenProgress::new(self.cfg_size, &stats, quota.attempts_budget(), elapsed, eta));
+        }
+        if !quota.is_met(stats.lr1, stats.lrk) {
+            warn!("quota not met (size: {}): found {}/{} lr(1) and {}/{} lr(k) grammars in {} attempts",
+                  self.cfg_size, stats.lr1, quota.lr1, stats.lrk, quota.lrk, stats.attempted);
+        }
+
+        Ok(CfgGenResult::new(cfg_result, work_dir, self.cfg_size, stats))
     }
 }
 
+/// Check that `tokens` can be declared with `%token` (each is a distinct
+/// identifier, other than yacc's `error`), and that there are enough of them,
+/// and of the non-terminal names they leave free, for CFGs of `cfg_size`
+/// rules.
+fn check_tokens(tokens: &[String], cfg_size: usize) -> Result<(), CfgGenError> {
+    for (i, tok) in tokens.iter().enumerate() {
+        let ident = match tok.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                tok.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            _ => false,
+        };
+        // `error` is yacc's error token, and `root` the generated start rule
+        if !ident || tok == "error" || tok == "root" {
+            return Err(CfgGenError::new(format!("{:?} cannot be used as a token name", tok)));
+        }
+        if tokens[..i].contains(tok) {
+            return Err(CfgGenError::new(format!("Token {} is given more than once", tok)));
+        }
+    }
+    if !tokens.is_empty() && tokens.len() < cfg_size {
+        return Err(CfgGenError::new(
+            format!("CFGs of size {} need {} tokens, but only {} are given", cfg_size, cfg_size, tokens.len())
+        ));
+    }
+    let non_terms = ASCII_UPPER.iter().filter(|c| !tokens.contains(&c.to_string())).count();
+    if non_terms + 1 < cfg_size {
+        return Err(CfgGenError::new(
+            format!("CFGs of size {} need {} non-terminal names, but only {} are free", cfg_size, cfg_size - 1,
+                    non_terms)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Write sentences covering `cfg` to `{cfg_f}.tests`, and near misses of
+/// them which must be rejected to `{cfg_f}.rejects`, one per line with the
+/// terminals separated by spaces.
+fn write_test_suite(cfg: &Cfg, cfg_f: &str) -> Result<(), CfgGenError> {
+    let sentences: Vec<Vec<String>> = cfg.test_suite(Coverage::Pairs)
+        .samples
+        .into_iter()
+        .map(|sample| sample.sentence)
+        .collect();
+    let rejects: Vec<Vec<String>> = cfg.near_misses(&sentences)
+        .into_iter()
+        .map(|miss| miss.tokens)
+        .collect();
+    for (ext, strings) in &[("tests", sentences), ("rejects", rejects)] {
+        let lines: String = strings.iter().map(|s| format!("{}\n", s.join(" "))).collect();
+        let path = format!("{}.{}", cfg_f, ext);
+        fs::write(&path, lines)
+            .map_err(|e| CfgGenError::new(
+                format!("Unable to write test suite {}, error:\n{}", path, e)
+            ))?;
+    }
+
+    Ok(())
+}
+
+/// Write lexers for `cfg` to `{cfg_f}.lrlex.l` (for lrlex) and
+/// `{cfg_f}.flex.l` (for flex), and its terminals to `{cfg_f}.tokens`.
+fn write_lexers(cfg: &Cfg, cfg_f: &str) -> Result<(), CfgGenError> {
+    for (ext, text) in &[("lrlex.l", cfg.as_lrlex()), ("flex.l", cfg.as_flex()), ("tokens", cfg.as_token_list())] {
+        let path = format!("{}.{}", cfg_f, ext);
+        fs::write(&path, text)
+            .map_err(|e| CfgGenError::new(
+                format!("Unable to write lexer {}, error:\n{}", path, e)
+            ))?;
+    }
+
+    Ok(())
+}
+
+/// Write the counterexamples for the LR(1) conflicts of `res` to
+/// `{cfg_f}.conflicts`, one after another.
+fn write_conflicts(res: &CfgLr1Result, cfg_f: &str) -> Result<(), CfgGenError> {
+    let text: Vec<String> = res.conflict_examples().iter().map(|example| example.to_string()).collect();
+    let path = format!("{}.conflicts", cfg_f);
+    fs::write(&path, text.join("\n"))
+        .map_err(|e| CfgGenError::new(
+            format!("Unable to write conflicts {}, error:\n{}", path, e)
+        ))
+}
+
 fn rand_alphanumeric(str_len: usize) -> String {
     thread_rng()
         .sample_iter(Alphanumeric)
         .take(str_len)
         .collect()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gen_named_tokens() {
+        let tokens: Vec<String> = ["ID", "NUM", "IF", "E"].iter().map(|tok| tok.to_string()).collect();
+        let cfg_gen = CfgGen::new(4, &tokens).unwrap();
+        assert!(!cfg_gen.non_terms.contains(&"E".to_string()));
+        let terms: Vec<String> = cfg_gen.lex_syms
+            .iter()
+            .filter_map(|sym| match sym {
+                LexSymbol::Term(t) if t.named => Some(t.tok.to_string()),
+                LexSymbol::Term(_) => panic!("{} is not a named token", sym),
+                LexSymbol::NonTerm(_) => None,
+            })
+            .collect();
+        assert_eq!(terms.len(), 4);
+        assert!(terms.iter().all(|tok| tokens.contains(tok)));
+        assert_eq!(cfg_gen.non_terms.len(), 3);
+
+        // too few tokens, or too few names left for the non-terminals
+        assert!(CfgGen::new(5, &tokens).is_err());
+        let letters: Vec<String> = ASCII_UPPER.iter().map(|c| c.to_string()).collect();
+        assert!(CfgGen::new(2, &letters).is_err());
+
+        assert!(CfgGen::new(4, &["'+'".to_string()]).is_err());
+        assert!(CfgGen::new(4, &["error".to_string()]).is_err());
+        let with_root: Vec<String> = ["ID", "NUM", "IF", "root"].iter().map(|tok| tok.to_string()).collect();
+        assert!(CfgGen::new(4, &with_root).is_err());
+        assert!(CfgGen::new(4, &["ID".to_string(), "ID".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_conflict_examples() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' E | 'x';\n").unwrap();
+        let res = CfgLr1Result::new(cfg, "x.bison.y".to_string(), "x.hyacc.y".to_string(),
+                                    false, false, true, false);
+        assert!(res.conflict_examples.get().is_none());
+        assert_eq!(res.conflict_examples().len(), 1);
+        assert!(res.conflict_examples()[0].is_unifying());
+
+        let dir = tempfile::tempdir().unwrap();
+        let cfg_f = dir.path().join("x").to_str().unwrap().to_string();
+        write_conflicts(&res, &cfg_f).unwrap();
+        assert_eq!(fs::read_to_string(format!("{}.conflicts", cfg_f)).unwrap(),
+                   res.conflict_examples()[0].to_string());
+    }
+
+    #[test]
+    fn test_quota_is_met() {
+        let quota = CfgGenQuota::new(2, 1).max_attempts(100);
+        assert_eq!(quota.max_attempts, Some(100));
+        assert_eq!(quota.attempts_budget(), Some(100));
+        assert_eq!(CfgGenQuota::new(2, 1).attempts_budget(), Some(DEFAULT_MAX_ATTEMPTS));
+        assert_eq!(CfgGenQuota::new(2, 1).max_time(Duration::from_secs(1)).attempts_budget(), None);
+        assert!(!quota.is_met(2, 0));
+        assert!(!quota.is_met(1, 1));
+        assert!(quota.is_met(2, 1));
+    }
+
+    #[test]
+    fn test_quota_remaining_attempts() {
+        let mut stats = CfgGenStats::default();
+        for _ in 0..10 {
+            stats.record_cfg(&[]);
+        }
+        stats.lr1 = 2;
+        assert_eq!(CfgGenQuota::new(4, 0).remaining_attempts(&stats), Some(10));
+        assert_eq!(CfgGenQuota::new(4, 0).max_attempts(15).remaining_attempts(&stats), Some(5));
+        assert_eq!(CfgGenQuota::new(4, 1).remaining_attempts(&stats), Some(DEFAULT_MAX_ATTEMPTS - 10));
+        assert_eq!(CfgGenQuota::new(4, 1).max_time(Duration::from_secs(1)).remaining_attempts(&stats), None);
+        assert_eq!(CfgGenQuota::new(4, 1).max_attempts(15).remaining_attempts(&stats), Some(5));
+        assert_eq!(CfgGenQuota::new(2, 0).remaining_attempts(&stats), Some(0));
+    }
+}
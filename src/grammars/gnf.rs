@@ -0,0 +1,182 @@
+//! Conversion to Greibach Normal Form, where every alternative is a terminal
+//! followed by non-terminals (plus, for the start rule only, the empty
+//! alternative).
+//!
+//! The CFG is first converted to CNF. Its non-terminals are then put in order
+//! A1..An, and left recursion is removed: alternatives of Ai starting with an
+//! earlier Aj which leads back to Ai are expanded, and direct left recursion
+//! is replaced by right recursion. Finally, working back from the rules whose
+//! alternatives all start with a terminal, leading non-terminals are expanded
+//! until every alternative starts with a terminal.
+
+use crate::grammars::{Cfg, CfgSize, LexSymbol};
+use crate::grammars::leftrec::{expand_first, left_corner_order, left_reaches, remove_direct_left_recursion};
+use crate::grammars::transform::{FreshNames, reachable_cfg, sym_rules, SymRule};
+
+/// Most alternatives `to_gnf` builds before giving up
+const MAX_GNF_ALTS: usize = 10_000;
+
+/// A CFG in Greibach Normal Form, along with how much it grew in the conversion.
+#[derive(Debug, Clone)]
+pub struct GnfCfg {
+    cfg: Cfg,
+    original_size: CfgSize,
+}
+
+impl GnfCfg {
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    /// The size of the CFG it was converted from
+    pub fn original_size(&self) -> CfgSize {
+        self.original_size
+    }
+
+    pub fn size(&self) -> CfgSize {
+        self.cfg.size()
+    }
+
+    /// How many times bigger (in symbols) the GNF grammar is than the original.
+    pub fn growth(&self) -> f64 {
+        self.size().syms as f64 / self.original_size.syms.max(1) as f64
+    }
+}
+
+impl Cfg {
+    /// Convert to Greibach Normal Form, or `None` if the result would have
+    /// more than `MAX_GNF_ALTS` alternatives (the expansion can blow up
+    /// exponentially).
+    pub fn to_gnf(&self) -> Option<GnfCfg> {
+        let cnf = self.to_cnf();
+        let mut names = FreshNames::new(cnf.cfg());
+        let mut rules = sym_rules(cnf.cfg());
+        let n = rules.len();
+        let expand = |rules: &mut Vec<SymRule>, i: usize, nt: &str, nt_alts: &[Vec<LexSymbol>]| {
+            let alts = std::mem::take(&mut rules[i].1);
+            rules[i].1 = expand_first(alts, nt, nt_alts);
+            rules.iter().map(|(_, alts)| alts.len()).sum::<usize>() <= MAX_GNF_ALTS
+        };
+
+        // remove left recursion
+        let mut rest_rules = Vec::<SymRule>::new();
+        for i in 0..n {
+            for j in 0..i {
+                if left_reaches(&rules, j, i) {
+                    let (nt, nt_alts) = rules[j].clone();
+                    if !expand(&mut rules, i, &nt, &nt_alts) {
+                        return None;
+                    }
+                }
+            }
+            if let Some(rest_rule) = remove_direct_left_recursion(&mut rules[i], &mut names) {
+                rest_rules.push(rest_rule);
+            }
+        }
+        rules.append(&mut rest_rules);
+        // each rule comes after the ones its alternatives start with, which
+        // by then start with a terminal
+        for i in left_corner_order(&rules) {
+            let mut leading = Vec::<String>::new();
+            for alt in &rules[i].1 {
+                if let Some(LexSymbol::NonTerm(nt)) = alt.first() {
+                    if !leading.contains(&nt.tok) {
+                        leading.push(nt.tok.to_string());
+                    }
+                }
+            }
+            for nt in leading {
+                let nt_alts = match rules.iter().find(|(lhs, _)| *lhs == nt) {
+                    Some((_, nt_alts)) => nt_alts.clone(),
+                    None => continue,
+                };
+                if !expand(&mut rules, i, &nt, &nt_alts) {
+                    return None;
+                }
+            }
+        }
+
+        Some(GnfCfg {
+            cfg: reachable_cfg(rules),
+            original_size: self.size(),
+        })
+    }
+
+    /// Does every alternative start with a terminal followed only by
+    /// non-terminals (or, for the start rule only, is it empty)?
+    pub fn is_gnf(&self) -> bool {
+        let start = match self.start_rule() {
+            Some(rule) => rule.lhs.as_str(),
+            None => return true,
+        };
+        self.rules.iter().all(|rule| {
+            rule.rhs.iter().all(|alt| match alt.lex_symbols.split_first() {
+                None => rule.lhs == start,
+                Some((LexSymbol::Term(_), rest)) => rest.iter().all(|sym| match sym {
+                    LexSymbol::NonTerm(nt) => nt.tok != start,
+                    LexSymbol::Term(_) => false,
+                }),
+                Some((LexSymbol::NonTerm(_), _)) => false,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gnf_simple() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' B 'c' | 'd' 'e';\nB: 'b';\n").unwrap();
+        let gnf = cfg.to_gnf().unwrap();
+        assert!(gnf.cfg().is_gnf());
+        assert_eq!(gnf.cfg().to_string(),
+                   "S: 'a' S_bin | 'd' T_e\n;\nT_c: 'c'\n;\nT_e: 'e'\n;\nS_bin: 'b' T_c\n;\n");
+        assert_eq!(gnf.original_size(), CfgSize { rules: 2, alts: 3, syms: 6 });
+        assert_eq!(gnf.size(), CfgSize { rules: 4, alts: 5, syms: 8 });
+    }
+
+    #[test]
+    fn test_gnf_left_recursion() {
+        // E: E '+' T | T; T: 'x'
+        let cfg = Cfg::from_yacc("%%\nE: E '+' T | T;\nT: 'x';\n").unwrap();
+        let gnf = cfg.to_gnf().unwrap();
+        assert!(gnf.cfg().is_gnf());
+        assert_eq!(gnf.cfg().to_string(),
+                   "E_start: 'x' E_bin | 'x' E_rest E_bin | 'x'\n;\nT: 'x'\n;\nE_bin: '+' T\n;\n\
+                    E_rest: '+' T | '+' T E_rest\n;\n");
+        assert!(gnf.growth() > 1.0);
+    }
+
+    #[test]
+    fn test_gnf_indirect_left_recursion() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a' | 'b';\nA: S 'c' | 'd';\n").unwrap();
+        let gnf = cfg.to_gnf().unwrap();
+        assert!(gnf.cfg().is_gnf());
+        assert_eq!(gnf.cfg().enumerate(5).sentences(), cfg.enumerate(5).sentences());
+    }
+
+    #[test]
+    fn test_gnf_nullable_start() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S 'b' | ;\n").unwrap();
+        let gnf = cfg.to_gnf().unwrap();
+        assert!(gnf.cfg().is_gnf());
+        assert!(gnf.cfg().to_string().starts_with("S_start:  | 'a' "));
+    }
+
+    #[test]
+    fn test_gnf_many_left_corners() {
+        // 13 rules and 94 alternatives in CNF, and far more in GNF
+        let cfg = Cfg::from_yacc("%%\nS: 'b' B 'a' | | A B A;\nA: | 'b' B;\nB: 'c' | | C A;\n\
+                                  C: 'c' C B | S 'c' 'a' | S A B;\n").unwrap();
+        assert!(cfg.to_gnf().is_none());
+    }
+
+    #[test]
+    fn test_is_gnf() {
+        assert!(Cfg::from_yacc("%%\nS: 'a' A B | ;\nA: 'a';\nB: 'b' A;\n").unwrap().is_gnf());
+        assert!(!Cfg::from_yacc("%%\nS: A 'b';\nA: 'a';\n").unwrap().is_gnf());
+        assert!(!Cfg::from_yacc("%%\nS: 'a' 'b';\n").unwrap().is_gnf());
+    }
+}
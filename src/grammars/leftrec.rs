@@ -0,0 +1,369 @@
+//! Finding and removing left recursion.
+//!
+//! A non-terminal `A` is left-recursive if `A` derives a sentential form
+//! starting with `A`. Besides `A: A x` (direct) and `A: B x; B: A y`
+//! (indirect), this includes left recursion hidden behind nullable symbols,
+//! as in `A: B A x` with `B` nullable.
+
+use std::collections::HashSet;
+
+use crate::grammars::{Cfg, LexSymbol, NonTermSymbol};
+use crate::grammars::cnf::eps_unit_free;
+use crate::grammars::transform::{FreshNames, nullable, reachable_cfg, sym_rules, SymRule};
+
+fn starts_with(alt: &[LexSymbol], nt: &str) -> bool {
+    matches!(alt.first(), Some(LexSymbol::NonTerm(first)) if first.tok == nt)
+}
+
+/// Replace the alternatives of `alts` starting with `nt` by one alternative
+/// for each of `nt_alts`.
+pub(crate) fn expand_first(alts: Vec<Vec<LexSymbol>>, nt: &str, nt_alts: &[Vec<LexSymbol>])
+                           -> Vec<Vec<LexSymbol>> {
+    let mut expanded = Vec::<Vec<LexSymbol>>::new();
+    let mut seen = HashSet::<Vec<LexSymbol>>::new();
+    for alt in alts {
+        let new_alts = if starts_with(&alt, nt) {
+            nt_alts
+                .iter()
+                .map(|nt_alt| nt_alt.iter().chain(alt[1..].iter()).cloned().collect())
+                .collect()
+        } else {
+            vec![alt]
+        };
+        for new_alt in new_alts {
+            if seen.insert(new_alt.clone()) {
+                expanded.push(new_alt);
+            }
+        }
+    }
+
+    expanded
+}
+
+/// Turn `A: A a1 | .. | A am | b1 | .. | bn` into `A: b1 | b1 Z | .. | bn | bn Z`
+/// and `Z: a1 | a1 Z | .. | am | am Z`, returning the new rule for `Z`.
+/// If `A` has no `bi`, it derives nothing and is left with no alternatives.
+pub(crate) fn remove_direct_left_recursion(rule: &mut SymRule, names: &mut FreshNames) -> Option<SymRule> {
+    let (lhs, alts) = rule;
+    let (rec, non_rec): (Vec<Vec<LexSymbol>>, Vec<Vec<LexSymbol>>) = alts
+        .drain(..)
+        .partition(|alt| starts_with(alt, lhs));
+    if rec.is_empty() || non_rec.is_empty() {
+        *alts = non_rec;
+        return None;
+    }
+
+    let rest = names.fresh(&format!("{}_rest", lhs));
+    let rest_sym = LexSymbol::NonTerm(NonTermSymbol::new(rest.to_string()));
+    let with_rest = |alt: &Vec<LexSymbol>| {
+        let mut alt_rest = alt.clone();
+        alt_rest.push(rest_sym.clone());
+        vec![alt.clone(), alt_rest]
+    };
+    *alts = non_rec.iter().flat_map(with_rest).collect();
+    let rest_alts = rec
+        .iter()
+        .map(|alt| alt[1..].to_vec())
+        .flat_map(|alt| with_rest(&alt))
+        .collect();
+
+    Some((rest, rest_alts))
+}
+
+/// Which symbols of an alternative count as its left corners.
+#[derive(Copy, Clone, PartialEq)]
+enum Corners {
+    /// The first symbol only
+    First,
+    /// Any symbol preceded only by nullable symbols
+    Nullable,
+    /// Any symbol preceded only by nullable symbols, and by at least one
+    Hidden,
+    /// Any symbol preceded and followed only by nullable symbols
+    Unit,
+}
+
+/// For each rule, the (indices of the) non-terminals which are left corners
+/// of its alternatives.
+fn left_corners(rules: &[SymRule], corners: Corners) -> Vec<Vec<usize>> {
+    let nullable = {
+        let rules: Vec<(&str, Vec<&[LexSymbol]>)> = rules
+            .iter()
+            .map(|(lhs, alts)| (lhs.as_str(), alts.iter().map(|alt| alt.as_slice()).collect()))
+            .collect();
+        nullable(&rules)
+    };
+    let is_nullable = |sym: &LexSymbol| matches!(sym, LexSymbol::NonTerm(nt) if nullable.contains(&nt.tok));
+    let rule_idx = |nt: &str| rules.iter().position(|(lhs, _)| lhs == nt);
+
+    rules
+        .iter()
+        .map(|(_, alts)| {
+            let mut rule_corners = Vec::<usize>::new();
+            for alt in alts {
+                for (i, sym) in alt.iter().enumerate() {
+                    if let LexSymbol::NonTerm(nt) = sym {
+                        let is_corner = match corners {
+                            Corners::Unit => alt[i + 1..].iter().all(is_nullable),
+                            Corners::Hidden => i > 0,
+                            _ => true,
+                        };
+                        if let Some(ridx) = rule_idx(&nt.tok) {
+                            if is_corner && !rule_corners.contains(&ridx) {
+                                rule_corners.push(ridx);
+                            }
+                        }
+                    }
+                    if corners == Corners::First || !is_nullable(sym) {
+                        break;
+                    }
+                }
+            }
+            rule_corners
+        })
+        .collect()
+}
+
+/// The elementary cycles of `edges`, each starting from its lowest node.
+fn cycles(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    fn walk(edges: &[Vec<usize>], start: usize, path: &mut Vec<usize>, found: &mut Vec<Vec<usize>>) {
+        let node = *path.last().expect("path starts with `start`");
+        for &next in &edges[node] {
+            if next == start {
+                found.push(path.clone());
+            } else if next > start && !path.contains(&next) {
+                path.push(next);
+                walk(edges, start, path, found);
+                path.pop();
+            }
+        }
+    }
+
+    let mut found = Vec::<Vec<usize>>::new();
+    for start in 0..edges.len() {
+        walk(edges, start, &mut vec![start], &mut found);
+    }
+
+    found
+}
+
+/// Is there a path from `from` to `to` in `edges`?
+fn reaches(edges: &[Vec<usize>], from: usize, to: usize) -> bool {
+    let mut seen = HashSet::<usize>::new();
+    let mut todo = vec![from];
+    while let Some(node) = todo.pop() {
+        if node == to {
+            return true;
+        }
+        if seen.insert(node) {
+            todo.extend(edges[node].iter().cloned());
+        }
+    }
+
+    false
+}
+
+/// Can `from` derive a sentential form starting with `to`?
+pub(crate) fn left_reaches(rules: &[SymRule], from: usize, to: usize) -> bool {
+    reaches(&left_corners(rules, Corners::First), from, to)
+}
+
+/// Is some rule left-recursive through an alternative whose left corner
+/// follows a nullable prefix, as in `A: B A x` with `B` nullable?
+fn has_hidden_left_recursion(rules: &[SymRule]) -> bool {
+    let corners = left_corners(rules, Corners::Nullable);
+    left_corners(rules, Corners::Hidden)
+        .iter()
+        .enumerate()
+        .any(|(ridx, hidden)| hidden.iter().any(|&corner| reaches(&corners, corner, ridx)))
+}
+
+/// The (indices of the) rules, each after the rules its alternatives start
+/// with. Needs a CFG with no left recursion.
+pub(crate) fn left_corner_order(rules: &[SymRule]) -> Vec<usize> {
+    fn visit(corners: &[Vec<usize>], ridx: usize, seen: &mut [bool], order: &mut Vec<usize>) {
+        if seen[ridx] {
+            return;
+        }
+        seen[ridx] = true;
+        for &corner in &corners[ridx] {
+            visit(corners, corner, seen, order);
+        }
+        order.push(ridx);
+    }
+
+    let corners = left_corners(rules, Corners::First);
+    let mut seen = vec![false; rules.len()];
+    let mut order = Vec::<usize>::with_capacity(rules.len());
+    for ridx in 0..rules.len() {
+        visit(&corners, ridx, &mut seen, &mut order);
+    }
+
+    order
+}
+
+/// Drop the rules with no alternatives, and the alternatives using them.
+fn remove_empty_rules(rules: &mut Vec<SymRule>) {
+    loop {
+        let empty: HashSet<String> = rules
+            .iter()
+            .filter(|(_, alts)| alts.is_empty())
+            .map(|(lhs, _)| lhs.to_string())
+            .collect();
+        if empty.is_empty() {
+            return;
+        }
+        rules.retain(|(lhs, _)| !empty.contains(lhs));
+        for (_, alts) in rules.iter_mut() {
+            alts.retain(|alt| !alt.iter().any(|sym|
+                matches!(sym, LexSymbol::NonTerm(nt) if empty.contains(&nt.tok))));
+        }
+    }
+}
+
+impl Cfg {
+    /// The left-recursive cycles, each as the non-terminals `A1, .., An`
+    /// where `A1` derives a sentential form starting with `A2` and so on,
+    /// and `An` one starting with `A1`.
+    pub fn left_recursive_cycles(&self) -> Vec<Vec<String>> {
+        let rules = sym_rules(self);
+        cycles(&left_corners(&rules, Corners::Nullable))
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(|ridx| rules[ridx].0.to_string()).collect())
+            .collect()
+    }
+
+    pub fn is_left_recursive(&self) -> bool {
+        !self.left_recursive_cycles().is_empty()
+    }
+
+    /// Is there left recursion other than direct left recursion through the
+    /// first symbol of an alternative (as some parser generators handle)?
+    pub(crate) fn has_indirect_left_recursion(&self) -> bool {
+        let rules = sym_rules(self);
+        cycles(&left_corners(&rules, Corners::First)).iter().any(|cycle| cycle.len() > 1)
+            || has_hidden_left_recursion(&rules)
+            || !cycles(&left_corners(&rules, Corners::Unit)).is_empty()
+    }
+
+    /// An equivalent CFG with no left recursion.
+    ///
+    /// Left recursion is removed one non-terminal at a time: alternatives
+    /// starting with an earlier non-terminal which leads back to the current
+    /// one are expanded, and then direct left recursion is replaced by right
+    /// recursion through a fresh non-terminal. This needs a CFG without
+    /// cycles or hidden left recursion: if there are either, the empty and
+    /// unit alternatives are removed first.
+    pub fn remove_left_recursion(&self) -> Cfg {
+        let mut rules = sym_rules(self);
+        let has_hidden = has_hidden_left_recursion(&rules);
+        let has_cycles = !cycles(&left_corners(&rules, Corners::Unit)).is_empty();
+        let cfg = if has_hidden || has_cycles {
+            let cfg = eps_unit_free(self);
+            rules = sym_rules(&cfg);
+            cfg
+        } else {
+            self.clone()
+        };
+
+        let mut names = FreshNames::new(&cfg);
+        let mut rest_rules = Vec::<SymRule>::new();
+        for i in 0..rules.len() {
+            for j in 0..i {
+                if left_reaches(&rules, j, i) {
+                    let (nt, nt_alts) = rules[j].clone();
+                    let alts = std::mem::take(&mut rules[i].1);
+                    rules[i].1 = expand_first(alts, &nt, &nt_alts);
+                }
+            }
+            if let Some(rest_rule) = remove_direct_left_recursion(&mut rules[i], &mut names) {
+                rest_rules.push(rest_rule);
+            }
+        }
+        rules.append(&mut rest_rules);
+        let start = rules.first().map(|(lhs, _)| lhs.to_string());
+        remove_empty_rules(&mut rules);
+        if rules.first().map(|(lhs, _)| lhs.to_string()) != start {
+            // the start rule derives nothing, so neither does the CFG
+            rules.clear();
+        }
+        for (_, alts) in rules.iter_mut() {
+            let mut seen = HashSet::<Vec<LexSymbol>>::new();
+            alts.retain(|alt| seen.insert(alt.clone()));
+        }
+
+        reachable_cfg(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_left_recursive_cycles() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a' | S 'b' | 'c';\nA: S 'd' | B A;\nB: 'e' | ;\n").unwrap();
+        assert_eq!(cfg.left_recursive_cycles(),
+                   vec![vec!["S".to_string(), "A".to_string()], vec!["S".to_string()], vec!["A".to_string()]]);
+        assert!(!Cfg::from_yacc("%%\nS: 'a' S | 'b';\n").unwrap().is_left_recursive());
+        assert!(cfg.has_indirect_left_recursion());
+        // hidden left recursion
+        assert!(Cfg::from_yacc("%%\nS: B S 'a' | 'c';\nB: 'e' | ;\n").unwrap().has_indirect_left_recursion());
+        assert!(!Cfg::from_yacc("%%\nS: S 'a' | B 'c';\nB: 'e' | ;\n").unwrap().has_indirect_left_recursion());
+    }
+
+    #[test]
+    fn test_remove_direct_left_recursion() {
+        let cfg = Cfg::from_yacc("%%\nE: E '+' T | T;\nT: T '*' F | F;\nF: '(' E ')' | 'x';\n").unwrap();
+        let no_lr = cfg.remove_left_recursion();
+        assert!(!no_lr.is_left_recursive());
+        assert_eq!(no_lr.to_string(),
+                   "E: T | T E_rest\n;\nT: F | F T_rest\n;\nF: '(' E ')' | 'x'\n;\n\
+                    E_rest: '+' T | '+' T E_rest\n;\nT_rest: '*' F | '*' F T_rest\n;\n");
+    }
+
+    #[test]
+    fn test_remove_left_recursion_no_duplicates() {
+        let cfg = Cfg::from_yacc("%%\nS: S 'x' | | ;\n").unwrap();
+        assert_eq!(cfg.remove_left_recursion().to_string(),
+                   "S:  | S_rest\n;\nS_rest: 'x' | 'x' S_rest\n;\n");
+    }
+
+    #[test]
+    fn test_remove_indirect_left_recursion() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a' | 'b';\nA: S 'c' | 'd';\n").unwrap();
+        let no_lr = cfg.remove_left_recursion();
+        assert!(!no_lr.is_left_recursive());
+        assert_eq!(no_lr.to_string(),
+                   "S: A 'a' | 'b'\n;\nA: 'b' 'c' | 'b' 'c' A_rest | 'd' | 'd' A_rest\n;\n\
+                    A_rest: 'a' 'c' | 'a' 'c' A_rest\n;\n");
+    }
+
+    #[test]
+    fn test_remove_hidden_left_recursion() {
+        // S is left-recursive through the nullable B
+        let cfg = Cfg::from_yacc("%%\nS: B S 'x' | 'y';\nB: 'b' | ;\n").unwrap();
+        assert!(cfg.is_left_recursive());
+        assert!(!cfg.remove_left_recursion().is_left_recursive());
+
+        // S is also directly left-recursive
+        let cfg = Cfg::from_yacc("%%\nS: 'c' S S | S S 'b' | ;\n").unwrap();
+        assert!(cfg.has_indirect_left_recursion());
+        let no_lr = cfg.remove_left_recursion();
+        assert!(!no_lr.is_left_recursive());
+        assert_eq!(no_lr.enumerate(5).sentences(), cfg.enumerate(5).sentences());
+
+        // S and A form a cycle
+        let cfg = Cfg::from_yacc("%%\nS: A | 'a';\nA: S | 'b' A;\n").unwrap();
+        assert!(cfg.is_left_recursive());
+        assert!(!cfg.remove_left_recursion().is_left_recursive());
+    }
+
+    #[test]
+    fn test_unproductive_left_recursion() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a' | 'b';\nA: A 'c';\n").unwrap();
+        assert_eq!(cfg.remove_left_recursion().to_string(), "S: 'b'\n;\n");
+        let cfg = Cfg::from_yacc("%%\nS: S 'a';\nA: 'b' | ;\n").unwrap();
+        assert!(cfg.remove_left_recursion().rules.is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+//! A minimal logging facade. Everything cfgz reports goes through `log`, so
+//! library users can silence it (`set_max_level(None)`) or redirect it
+//! (`set_logger`).
+
+use std::fmt;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Receives the messages which pass the maximum level.
+/// Called from the rayon worker threads, hence `Send + Sync`.
+pub trait Logger: Send + Sync {
+    fn log(&self, level: Level, msg: &str);
+}
+
+/// Used until `set_logger` is called: info and debug go to stdout, warnings
+/// and errors to stderr, one line (or multi-line message) at a time.
+struct StdLogger;
+
+impl Logger for StdLogger {
+    fn log(&self, level: Level, msg: &str) {
+        match level {
+            Level::Error | Level::Warn => eprintln!("[{}] {}", level, msg),
+            Level::Info | Level::Debug => println!("{}", msg),
+        }
+    }
+}
+
+/// 0 means logging is switched off
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(Level::Info as usize);
+static LOGGER: RwLock<Option<Box<dyn Logger>>> = RwLock::new(None);
+
+/// Send all messages to `logger` instead of stdout/stderr, returning the
+/// logger set before (if any).
+pub fn set_logger(logger: Box<dyn Logger>) -> Option<Box<dyn Logger>> {
+    replace_logger(Some(logger))
+}
+
+fn replace_logger(logger: Option<Box<dyn Logger>>) -> Option<Box<dyn Logger>> {
+    std::mem::replace(&mut *LOGGER.write().expect("logger lock poisoned"), logger)
+}
+
+/// Only pass messages up to `level` to the logger; `None` silences cfgz.
+pub fn set_max_level(level: Option<Level>) {
+    MAX_LEVEL.store(level.map_or(0, |l| l as usize), Ordering::Relaxed);
+}
+
+fn enabled(level: Level) -> bool {
+    (level as usize) <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+pub(crate) fn log(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let msg = args.to_string();
+    match LOGGER.read().expect("logger lock poisoned").as_ref() {
+        Some(logger) => logger.log(level, &msg),
+        None => StdLogger.log(level, &msg),
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::grammars::log::log($crate::grammars::log::Level::Error, format_args!($($arg)*))
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::grammars::log::log($crate::grammars::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::grammars::log::log($crate::grammars::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::grammars::log::log($crate::grammars::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+/// Serialises the tests which set the logger or depend on what is logged,
+/// as the logger is shared by the whole test binary.
+#[cfg(test)]
+pub(crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    struct CaptureLogger {
+        msgs: Arc<Mutex<Vec<(Level, String)>>>,
+    }
+
+    impl Logger for CaptureLogger {
+        fn log(&self, level: Level, msg: &str) {
+            self.msgs.lock().unwrap().push((level, msg.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        let _lock = test_lock();
+        assert!(Level::Error < Level::Warn);
+        assert!(Level::Info < Level::Debug);
+        assert!(enabled(Level::Info));
+        assert!(!enabled(Level::Debug));
+    }
+
+    #[test]
+    fn test_set_logger() {
+        let _lock = test_lock();
+        let msgs = Arc::new(Mutex::new(Vec::new()));
+        let prev = set_logger(Box::new(CaptureLogger { msgs: Arc::clone(&msgs) }));
+        error!("test_set_logger: {}", 1);
+        info!("test_set_logger: {}", 42);
+        debug!("test_set_logger: hidden");
+        replace_logger(prev);
+
+        let captured: Vec<(Level, String)> = msgs.lock().unwrap()
+            .iter()
+            .filter(|(_, msg)| msg.starts_with("test_set_logger"))
+            .cloned()
+            .collect();
+        assert_eq!(captured, vec![(Level::Error, "test_set_logger: 1".to_string()),
+                                  (Level::Info, "test_set_logger: 42".to_string())]);
+    }
+}
@@ -1,8 +1,8 @@
 use std::{fs, io, path::Path};
 use std::process::Command;
 
-use cfgrammar::yacc::YaccKind;
-use lrpar::CTParserBuilder;
+use cfgrammar::yacc::{YaccGrammar, YaccKind};
+use lrtable::{from_yacc, Minimiser};
 
 use crate::grammars::Cfg;
 use crate::grammars::gen::CfgLr1Result;
@@ -11,6 +11,8 @@ const BISON_CMD: &str = "/usr/bin/bison";
 const HYACC_CMD: &str = "/usr/local/bin/hyacc";
 const TIMEOUT_CMD: &str = "/usr/bin/timeout";
 const HYACC_TIMEOUT_SECS: usize = 5;
+/// exit status of `timeout` when the command timed out
+const TIMEOUT_EXIT_CODE: i32 = 124;
 
 fn run(cmd_path: &str, args: &[&str]) -> io::Result<(Option<i32>, String, String)> {
     let mut cmd = Command::new(cmd_path);
@@ -49,7 +51,9 @@ pub(crate) fn run_bison(cfg_path: &Path, out: bool) -> Result<(bool, String), io
     Ok((true, msg))
 }
 
-fn run_hyacc(cfg_path: &Path) -> Result<(bool, String), io::Error> {
+/// Run Hyacc (LR(k) mode) on `cfg_path`, returning whether it succeeded,
+/// whether it was killed for running over `HYACC_TIMEOUT_SECS`, and its output.
+fn run_hyacc(cfg_path: &Path) -> Result<(bool, bool, String), io::Error> {
     let inputp = cfg_path.to_str().unwrap();
     let hyacc_run_secs = HYACC_TIMEOUT_SECS.to_string();
     let args: &[&str] = &[hyacc_run_secs.as_str(), HYACC_CMD, inputp, "-K", "-c"];
@@ -64,7 +68,7 @@ fn run_hyacc(cfg_path: &Path) -> Result<(bool, String), io::Error> {
         if (*s).contains("laneHeadList is NULL") {
             let mut msg_lines: Vec<&str> = vec![(*s)];
             msg_lines.append(&mut k_lines);
-            return Ok((false, msg_lines.join("\n")));
+            return Ok((false, false, msg_lines.join("\n")));
         }
     }
 
@@ -73,7 +77,7 @@ fn run_hyacc(cfg_path: &Path) -> Result<(bool, String), io::Error> {
             let mut msg_lines: Vec<&str> = vec![];
             msg_lines.append(&mut k_lines);
             msg_lines.push(*l);
-            return Ok((true, msg_lines.join("\n")));
+            return Ok((true, false, msg_lines.join("\n")));
         }
     }
 
@@ -81,28 +85,45 @@ fn run_hyacc(cfg_path: &Path) -> Result<(bool, String), io::Error> {
                       s_code.unwrap_or(-1),
                       k_lines.join("\n"),
                       err);
-    Ok((false, msg))
+    Ok((false, s_code == Some(TIMEOUT_EXIT_CODE), msg))
 }
 
+/// Build grmtools' LR(1) state table for `cfg_path` in memory (as lrpar
+/// would, but without generating a parser), and check it has no conflicts.
 fn run_lrpar(cfg_path: &Path) -> (bool, String) {
-    let parse_res = CTParserBuilder::new()
-        .yacckind(YaccKind::Grmtools)
-        .process_file(cfg_path, "src/out");
-
-    match parse_res {
-        Ok(res) => {
-            return (true, format!("{:?}", res));
+    let src = match fs::read_to_string(cfg_path) {
+        Ok(src) => src,
+        Err(e) => {
+            return (false, format!("err: {}", e));
         }
+    };
+    let grm = match YaccGrammar::new(YaccKind::Grmtools, &src) {
+        Ok(grm) => grm,
         Err(e) => {
             return (false, format!("err: {}", e));
         }
+    };
+
+    match from_yacc(&grm, Minimiser::Pager) {
+        Ok((_, stable)) => {
+            match stable.conflicts() {
+                Some(c) => {
+                    (false, format!("err: {} Reduce/Reduce, {} Shift/Reduce",
+                                    c.rr_len(),
+                                    c.sr_len()))
+                }
+                None => {
+                    (true, "no conflicts".to_string())
+                }
+            }
+        }
+        Err(e) => {
+            (false, format!("err: {}", e))
+        }
     }
 }
 
 pub(crate) fn run_lr1_tools(cfg: Cfg, cfg_no: usize, temp_dir: &str) -> CfgLr1Result {
-    if (cfg_no % 100) == 0 {
-        eprint!(".");
-    }
     let lrparp_buf = Path::new(temp_dir).join(format!("{}.lrpar.y", cfg_no));
     let bisonp_buf = Path::new(temp_dir).join(format!("{}.bison.y", cfg_no));
     let hyaccp_buf = Path::new(temp_dir).join(format!("{}.hyacc.y", cfg_no));
@@ -121,12 +142,13 @@ pub(crate) fn run_lr1_tools(cfg: Cfg, cfg_no: usize, temp_dir: &str) -> CfgLr1Re
     let (lrpar_lr1, _) = run_lrpar(lrparp);
     let (bison_lr1, _) = run_bison(bisonp, false)
         .unwrap_or_else(|_| panic!("{} - Bison run failed!", bisonp.to_str().unwrap()));
-    let (hyacc_lr1, _) = run_hyacc(hyaccp)
+    let (hyacc_lr1, hyacc_timeout, _) = run_hyacc(hyaccp)
         .unwrap_or_else(|_| panic!("{} - Hyacc run failed!", hyaccp.to_str().unwrap()));
 
-    CfgLr1Result::new(bisonp.to_str().unwrap().to_owned(),
+    CfgLr1Result::new(cfg,
+                      bisonp.to_str().unwrap().to_owned(),
                       hyaccp.to_str().unwrap().to_owned(),
-                      lrpar_lr1, bison_lr1, hyacc_lr1)
+                      lrpar_lr1, bison_lr1, hyacc_lr1, hyacc_timeout)
 }
 
 #[cfg(test)]
@@ -312,7 +334,7 @@ mod tests {
         let _ = fs::write(&cfgp, cfg.as_hyacc().as_str())
             .expect("Unable to write cfg in hyacc format");
 
-        let (is_lr1, msg) = run_hyacc(cfgp)
+        let (is_lr1, _, msg) = run_hyacc(cfgp)
             .expect("Hyacc run failed!");
         println!("msg: {}", msg);
         assert!(is_lr1);
@@ -326,7 +348,7 @@ mod tests {
         let _ = fs::write(&cfgp, cfg.as_hyacc().as_str())
             .expect("Unable to write cfg in hyacc format");
 
-        let (is_lr2, msg) = run_hyacc(cfgp)
+        let (is_lr2, _, msg) = run_hyacc(cfgp)
             .expect("Hyacc run failed!");
         println!("msg: {}", msg);
         assert!(is_lr2);
@@ -340,7 +362,7 @@ mod tests {
         let _ = fs::write(&cfgp, cfg.as_hyacc().as_str())
             .expect("Unable to write cfg in hyacc format");
 
-        let (is_lr1, msg) = run_hyacc(cfgp)
+        let (is_lr1, _, msg) = run_hyacc(cfgp)
             .expect("Hyacc run failed!");
         println!("{}", msg);
         assert!(!is_lr1);
@@ -1,12 +1,41 @@
 use std::{fmt, io};
+use std::hash::{Hash, Hasher};
 
-use crate::grammars::gen::CfgGenError;
+use cfgrammar::Symbol;
+use cfgrammar::yacc::{YaccGrammar, YaccKind, YaccOriginalActionKind};
+
+use crate::grammars::gen::{CfgGenError, CfgGenOptions, CfgGenQuota};
+use crate::grammars::progress::CfgGenProgress;
 use std::path::Path;
 
+#[macro_use]
+pub(crate) mod log;
+pub(crate) mod automaton;
+pub(crate) mod cnf;
+pub(crate) mod counterexample;
+pub(crate) mod coverage;
+pub(crate) mod earley;
+pub(crate) mod enumerate;
+mod export;
+mod factor;
 pub(crate) mod gen;
+pub(crate) mod gnf;
+mod leftrec;
 mod lr1_check;
+pub(crate) mod negative;
+pub(crate) mod peg;
+pub(crate) mod progress;
+pub(crate) mod recognise;
+pub(crate) mod reduce;
+pub(crate) mod sample;
+mod stats;
+mod transform;
+#[cfg(test)]
+mod test_utils;
+pub(crate) mod tree;
+mod workdir;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 enum SymType {
     NonTerminal,
     Terminal,
@@ -43,30 +72,58 @@ impl PartialEq for NonTermSymbol {
     }
 }
 
+impl Eq for NonTermSymbol {}
+
+impl Hash for NonTermSymbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tok_type.hash(state);
+        self.tok.hash(state);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct TermSymbol {
     tok: String,
     tok_type: SymType,
+    /// A token declared with `%token` (such as `ID`), rather than a quoted
+    /// literal. No CFG uses a spelling both ways (`from_yacc` rejects such
+    /// grammars), so the analyses tell terminals apart by `tok` alone.
+    named: bool,
 }
 
 impl fmt::Display for TermSymbol {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "'{}'", &self.tok)
+        write!(f, "{}", term_str(&self.tok, self.named))
     }
 }
 
+/// A terminal as the yacc output writes it: a named token bare, and any
+/// other quoted.
+pub(crate) fn term_str(tok: &str, named: bool) -> String {
+    if named { tok.to_string() } else { format!("'{}'", tok) }
+}
+
 impl TermSymbol {
     fn new(tok: String) -> Self {
         Self {
             tok,
             tok_type: SymType::Terminal,
+            named: false,
+        }
+    }
+
+    fn named(tok: String) -> Self {
+        Self {
+            tok,
+            tok_type: SymType::Terminal,
+            named: true,
         }
     }
 }
 
 impl PartialEq for TermSymbol {
     fn eq(&self, other: &Self) -> bool {
-        if self.tok_type.eq(&other.tok_type) && self.tok.eq(&other.tok) {
+        if self.tok_type.eq(&other.tok_type) && self.tok.eq(&other.tok) && self.named == other.named {
             return true;
         }
 
@@ -74,7 +131,17 @@ impl PartialEq for TermSymbol {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Eq for TermSymbol {}
+
+impl Hash for TermSymbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tok_type.hash(state);
+        self.tok.hash(state);
+        self.named.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum LexSymbol {
     NonTerm(NonTermSymbol),
     Term(TermSymbol),
@@ -93,7 +160,7 @@ impl fmt::Display for LexSymbol {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct RuleAlt {
     lex_symbols: Vec<LexSymbol>,
 }
@@ -134,8 +201,8 @@ impl RuleAlt {
     }
 }
 
-#[derive(Debug)]
-struct CfgRule {
+#[derive(Debug, Clone)]
+pub(crate) struct CfgRule {
     lhs: String,
     rhs: Vec<RuleAlt>,
 }
@@ -174,6 +241,40 @@ impl CfgRule {
 }
 
 #[derive(Debug)]
+pub struct CfgError {
+    msg: String,
+}
+
+impl CfgError {
+    fn new(msg: String) -> Self {
+        Self {
+            msg
+        }
+    }
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+/// The size of a CFG
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CfgSize {
+    pub rules: usize,
+    pub alts: usize,
+    /// Symbols on the right-hand sides
+    pub syms: usize,
+}
+
+impl fmt::Display for CfgSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} rules, {} alternatives, {} symbols", self.rules, self.alts, self.syms)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Cfg {
     rules: Vec<CfgRule>,
 }
@@ -199,22 +300,106 @@ impl Cfg {
         }
     }
 
+    /// Read a CFG in the yacc format written by `as_hyacc`. Quoted tokens and
+    /// those declared with `%token` are terminals, and the `%start` rule (or
+    /// else the first rule) comes first. A name declared with `%token` must
+    /// not also be used quoted, as yacc takes both to be the same token.
+    pub fn from_yacc(src: &str) -> Result<Self, CfgError> {
+        let grm = YaccGrammar::new(YaccKind::Original(YaccOriginalActionKind::NoAction), src)
+            .map_err(|e| CfgError::new(format!("Unable to parse cfg, error:\n{}", e)))?;
+        let declared = declared_tokens(src);
+        let quoted = quoted_tokens(src);
+        if let Some(tok) = declared.iter().find(|tok| quoted.contains(tok)) {
+            return Err(CfgError::new(format!("Token {} is both declared with %token and quoted", tok)));
+        }
+        let start_ridx = match grm.prod(grm.start_prod())[0] {
+            Symbol::Rule(ridx) => ridx,
+            Symbol::Token(_) => unreachable!("the start production derives a rule"),
+        };
+        let mut ridxs = vec![start_ridx];
+        for ridx in grm.iter_rules() {
+            if ridx != start_ridx && ridx != grm.start_rule_idx() && Some(ridx) != grm.implicit_rule() {
+                ridxs.push(ridx);
+            }
+        }
+
+        let rules = ridxs
+            .into_iter()
+            .map(|ridx| {
+                let alts = grm.rule_to_prods(ridx)
+                    .iter()
+                    .map(|pidx| {
+                        let syms = grm.prod(*pidx)
+                            .iter()
+                            .map(|sym| match sym {
+                                Symbol::Rule(r) => LexSymbol::NonTerm(
+                                    NonTermSymbol::new(grm.rule_name(*r).to_string())),
+                                Symbol::Token(t) => {
+                                    let tok = grm.token_name(*t).unwrap_or_default().to_string();
+                                    if declared.contains(&tok) {
+                                        LexSymbol::Term(TermSymbol::named(tok))
+                                    } else {
+                                        LexSymbol::Term(TermSymbol::new(tok))
+                                    }
+                                }
+                            })
+                            .collect();
+                        RuleAlt::new(syms)
+                    })
+                    .collect();
+                CfgRule::new(grm.rule_name(ridx).to_string(), alts)
+            })
+            .collect();
+
+        Ok(Self::new(rules))
+    }
+
     fn start_rule(&self) -> Option<&CfgRule> {
         self.rules.first()
     }
 
-    fn as_hyacc(&self) -> String {
+    pub fn size(&self) -> CfgSize {
+        let alts = self.rules.iter().flat_map(|rule| rule.rhs.iter());
+        CfgSize {
+            rules: self.rules.len(),
+            alts: alts.clone().count(),
+            syms: alts.map(|alt| alt.lex_symbols.len()).sum(),
+        }
+    }
+
+    /// The named tokens used in the CFG, in order of first use.
+    pub(crate) fn named_tokens(&self) -> Vec<String> {
+        let mut toks = Vec::<String>::new();
+        for sym in self.rules.iter().flat_map(|rule| rule.rhs.iter()).flat_map(|alt| alt.lex_symbols.iter()) {
+            if let LexSymbol::Term(t) = sym {
+                if t.named && !toks.contains(&t.tok) {
+                    toks.push(t.tok.to_string());
+                }
+            }
+        }
+        toks
+    }
+
+    /// The `%token` declaration of the CFG's named tokens, if it has any.
+    fn token_decls(&self) -> String {
+        match self.named_tokens() {
+            toks if toks.is_empty() => String::new(),
+            toks => format!("%token {}\n", toks.join(" ")),
+        }
+    }
+
+    pub fn as_hyacc(&self) -> String {
         let s_rule = self.start_rule()
             .expect("Cfg is missing a start rule!");
 
-        format!("%start {}\n\n%%\n\n{}\n\n%%", s_rule.lhs, self)
+        format!("{}%start {}\n\n%%\n\n{}\n\n%%", self.token_decls(), s_rule.lhs, self)
     }
 
-    fn as_yacc(&self) -> String {
+    pub fn as_yacc(&self) -> String {
         format!("%define lr.type canonical-lr\n\n{}", self.as_hyacc())
     }
 
-    fn as_lrpar(&self) -> String {
+    pub fn as_lrpar(&self) -> String {
         let s_rule = self.start_rule()
             .expect("Cfg is missing a start rule!");
 
@@ -223,16 +408,101 @@ impl Cfg {
             s = format!("{}{}\n;\n", s, rule.as_lrpar());
         }
 
-        format!("%start {}\n\n%%\n\n{}\n\n%%", s_rule.lhs, s)
+        format!("{}%start {}\n\n%%\n\n{}\n\n%%", self.token_decls(), s_rule.lhs, s)
     }
 }
 
+/// The names declared with `%token` in the declarations of a yacc grammar.
+fn declared_tokens(src: &str) -> Vec<String> {
+    let decls = src.split("%%").next().unwrap_or_default();
+    decls
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("%token"))
+        .flat_map(|names| names.split_whitespace())
+        .filter(|name| !name.starts_with('<'))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// The quoted tokens of the rules section of yacc source `src`, read as
+/// cfgrammar reads them: comments and actions are skipped, and a literal
+/// runs to the next matching quote on the same line.
+fn quoted_tokens(src: &str) -> Vec<String> {
+    let chars: Vec<char> = match src.find("%%") {
+        Some(i) => src[i + 2..].chars().collect(),
+        None => return vec![],
+    };
+    let mut toks = Vec::<String>::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('%', Some('%')) => break,
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            ('{', _) => {
+                let mut depth = 0;
+                while i < chars.len() {
+                    match chars[i] {
+                        '{' => depth += 1,
+                        '}' if depth == 1 => break,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            (q, _) if q == '\'' || q == '"' => {
+                let end = (i + 2..chars.len())
+                    .take_while(|&j| chars[j] != '\n')
+                    .find(|&j| chars[j] == q);
+                match end {
+                    Some(j) => {
+                        toks.push(chars[i + 1..j].iter().collect());
+                        i = j + 1;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    toks
+}
+
 /// Generate `n` CFGs of size `cfg_sz` (`size` refers to the number of rules).
 /// The generated CFGs are saved in `out_dir` by size.
-pub fn generate(cfg_sz: usize, n: usize, out_dir: &str) -> Result<(), CfgGenError> {
-    println!("=> generating grammars (size: {}) in dir: {}", cfg_sz, &out_dir);
-    let cfg_gen = gen::CfgGen::new(cfg_sz);
-    let cfg_result = cfg_gen.gen_par(n);
+/// `progress` is called after every batch of CFGs.
+pub fn generate(cfg_sz: usize, n: usize, out_dir: &str, opts: &CfgGenOptions,
+                progress: &mut dyn FnMut(&CfgGenProgress)) -> Result<(), CfgGenError> {
+    info!("=> generating grammars (size: {}) in dir: {}", cfg_sz, &out_dir);
+    let cfg_gen = gen::CfgGen::new(cfg_sz, &opts.tokens)?;
+    let cfg_result = cfg_gen.gen_par(n, opts, progress)?;
+    cfg_result.write_results(out_dir)?;
+
+    Ok(())
+}
+
+/// Generate CFGs of size `cfg_sz` until `quota` is met (or its budget runs out).
+/// The generated CFGs are saved in `out_dir` by size.
+/// `progress` is called after every batch of CFGs.
+pub fn generate_quota(cfg_sz: usize, quota: &CfgGenQuota, out_dir: &str, opts: &CfgGenOptions,
+                      progress: &mut dyn FnMut(&CfgGenProgress)) -> Result<(), CfgGenError> {
+    info!("=> generating grammars (size: {}, quota: {} lr(1), {} lr(k)) in dir: {}",
+          cfg_sz, quota.lr1, quota.lrk, &out_dir);
+    let cfg_gen = gen::CfgGen::new(cfg_sz, &opts.tokens)?;
+    let cfg_result = cfg_gen.gen_quota(quota, opts, progress)?;
     cfg_result.write_results(out_dir)?;
 
     Ok(())
@@ -248,9 +518,11 @@ pub fn lr1_check(gp: &Path, out: bool) -> Result<bool, io::Error>  {
 
 #[cfg(test)]
 mod tests {
+    use cfgrammar::yacc::{YaccGrammar, YaccKind};
+
     use crate::grammars::{Cfg, CfgRule};
 
-    use super::{LexSymbol, NonTermSymbol, TermSymbol};
+    use super::{LexSymbol, NonTermSymbol, quoted_tokens, TermSymbol};
     use super::RuleAlt;
 
     fn test_alt_1() -> RuleAlt {
@@ -332,6 +604,17 @@ mod tests {
         assert_eq!(cfg.as_yacc(), cfg_expected);
     }
 
+    #[test]
+    fn test_cfg_from_yacc() {
+        let cfg = simple_cfg();
+        let parsed = Cfg::from_yacc(&cfg.as_hyacc()).unwrap();
+        assert_eq!(parsed.to_string(), cfg.to_string());
+
+        let parsed = Cfg::from_yacc("%start B\n%%\nS: B 'a' | ;\nB: 'b' S;\n").unwrap();
+        assert_eq!(parsed.to_string(), "B: 'b' S\n;\nS: B 'a' | \n;\n");
+        assert!(Cfg::from_yacc("%%\nS: 'a\n").is_err());
+    }
+
     #[test]
     fn test_cfg_lrpar() {
         let cfg = simple_cfg();
@@ -345,4 +628,23 @@ mod tests {
 
         assert_eq!(cfg.as_lrpar(), cfg_expected);
     }
+
+    #[test]
+    fn test_cfg_named_tokens() {
+        let cfg = Cfg::from_yacc("%token ID NUM\n%%\nE: E '+' T | T;\nT: ID | NUM | '(' E ')';\n").unwrap();
+        assert_eq!(cfg.named_tokens(), vec!["ID", "NUM"]);
+        assert_eq!(cfg.as_hyacc(),
+                   "%token ID NUM\n%start E\n\n%%\n\nE: E '+' T | T\n;\nT: ID | NUM | '(' E ')'\n;\n\n\n%%");
+        assert_eq!(Cfg::from_yacc(&cfg.as_hyacc()).unwrap().as_hyacc(), cfg.as_hyacc());
+        assert!(cfg.as_lrpar().starts_with("%token ID NUM\n%start E\n"));
+        assert!(YaccGrammar::<u32>::new(YaccKind::Grmtools, &cfg.as_lrpar()).is_ok());
+        // a spelling is either named or quoted
+        assert!(Cfg::from_yacc("%token ID\n%%\nS: ID | 'ID';\n").is_err());
+        assert!(Cfg::from_yacc("%token ID\n%%\nS: ID | \"ID\" /* ; */;\n").is_err());
+        // but not if the spelling only turns up in comments or other literals
+        let cfg = Cfg::from_yacc("%token B\n%%\nS: B \"'B'\" /* 'B' */ | 'b'; // \"B\"\n").unwrap();
+        assert_eq!(cfg.named_tokens(), vec!["B"]);
+        assert_eq!(quoted_tokens("%%\nS: 'a' \"'\" '\"' { \"x\" } 'b\n'c';\n%%\n'd'"),
+                   vec!["a", "'", "\"", "c"]);
+    }
 }
@@ -0,0 +1,127 @@
+//! Near misses: strings one token edit away from a sentence, but not
+//! themselves in the language, for checking that parsers reject what they
+//! should.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::grammars::{Cfg, LexSymbol};
+
+/// A single token edit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mutation {
+    /// Insert the token before the given position
+    Insert(usize, String),
+    Delete(usize),
+    /// Replace the token at the given position
+    Substitute(usize, String),
+}
+
+impl Mutation {
+    fn apply(&self, sentence: &[String]) -> Vec<String> {
+        let mut mutant = sentence.to_vec();
+        match self {
+            Mutation::Insert(pos, tok) => mutant.insert(*pos, tok.to_string()),
+            Mutation::Delete(pos) => {
+                mutant.remove(*pos);
+            }
+            Mutation::Substitute(pos, tok) => mutant[*pos] = tok.to_string(),
+        }
+        mutant
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mutation::Insert(pos, tok) => write!(f, "insert '{}' at {}", tok, pos),
+            Mutation::Delete(pos) => write!(f, "delete at {}", pos),
+            Mutation::Substitute(pos, tok) => write!(f, "substitute '{}' at {}", tok, pos),
+        }
+    }
+}
+
+/// A string not in the language, made from a sentence by one edit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    pub tokens: Vec<String>,
+    /// Index of the sentence it was made from
+    pub from: usize,
+    pub mutation: Mutation,
+}
+
+impl Cfg {
+    /// The terminals used in the CFG, in order of first use.
+    pub(crate) fn terminals(&self) -> Vec<String> {
+        let mut terms = Vec::<String>::new();
+        for sym in self.rules.iter().flat_map(|rule| rule.rhs.iter()).flat_map(|alt| alt.lex_symbols.iter()) {
+            if let LexSymbol::Term(t) = sym {
+                if !terms.contains(&t.tok) {
+                    terms.push(t.tok.to_string());
+                }
+            }
+        }
+        terms
+    }
+
+    /// Every string made from one of `sentences` by inserting, deleting or
+    /// substituting a single terminal of the CFG which the CFG does not
+    /// accept. A string made in more than one way is only given once.
+    pub fn near_misses(&self, sentences: &[Vec<String>]) -> Vec<NearMiss> {
+        let terms = self.terminals();
+        let recogniser = self.recogniser();
+        let mut seen: HashSet<Vec<String>> = sentences.iter().cloned().collect();
+        let mut misses = Vec::<NearMiss>::new();
+        for (from, sentence) in sentences.iter().enumerate() {
+            let mut mutations = Vec::<Mutation>::new();
+            for pos in 0..=sentence.len() {
+                mutations.extend(terms.iter().map(|tok| Mutation::Insert(pos, tok.to_string())));
+                if pos < sentence.len() {
+                    mutations.push(Mutation::Delete(pos));
+                    mutations.extend(terms
+                        .iter()
+                        .filter(|tok| **tok != sentence[pos])
+                        .map(|tok| Mutation::Substitute(pos, tok.to_string())));
+                }
+            }
+            for mutation in mutations {
+                let tokens = mutation.apply(sentence);
+                if seen.insert(tokens.clone()) && !recogniser.accepts(&tokens) {
+                    misses.push(NearMiss { tokens, from, mutation });
+                }
+            }
+        }
+
+        misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammars::test_utils::toks;
+
+    use super::*;
+
+    #[test]
+    fn test_near_misses() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S 'b' | 'c';\n").unwrap();
+        let misses = cfg.near_misses(&[toks("acb")]);
+        let strings: Vec<String> = misses.iter().map(|m| m.tokens.concat()).collect();
+        assert!(strings.contains(&"cb".to_string()));
+        assert!(strings.contains(&"aab".to_string()));
+        assert!(strings.contains(&"acbc".to_string()));
+        assert!(misses.iter().all(|m| !cfg.recogniser().accepts(&m.tokens)));
+        assert_eq!(misses.iter().find(|m| m.tokens == toks("cb")).unwrap().mutation, Mutation::Delete(0));
+
+        // sentences are of odd length, so no deletion gives one; but deleting
+        // either 'a' (or either 'b') of "aacbb" gives the same string
+        let misses = cfg.near_misses(&[toks("aacbb")]);
+        let deletions = misses.iter().filter(|m| matches!(m.mutation, Mutation::Delete(_))).count();
+        assert_eq!(deletions, 3);
+
+        // a mutant which is a sentence is not a near miss
+        let cfg = Cfg::from_yacc("%%\nS: 'a' | 'a' 'a';\n").unwrap();
+        let misses = cfg.near_misses(&[toks("a")]);
+        assert_eq!(misses.iter().map(|m| m.tokens.concat()).collect::<Vec<_>>(), vec![""]);
+    }
+}
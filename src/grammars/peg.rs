@@ -0,0 +1,217 @@
+//! What is lost reading a CFG as a PEG, with each rule's alternatives tried
+//! in order as an ordered choice.
+//!
+//! A PEG commits to the first alternative which matches, so it recognises
+//! a subset of the CFG's language: an alternative matching a prefix of what
+//! a later one would have matched shadows it, and the rest of the input may
+//! then fail to match. Both the sentences lost and the shadowed alternatives
+//! are found among the sentences up to a given length.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::grammars::{Cfg, CfgRule, term_str};
+use crate::grammars::transform::{FreshNames, IdxRules, Sym};
+
+/// An alternative which an earlier alternative of its rule can shadow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowedAlt {
+    pub rule: String,
+    /// Index of the shadowed alternative
+    pub alt: usize,
+    /// Index of the earlier alternative shadowing it
+    pub by: usize,
+    /// Matched by `by`, and a proper prefix of `sentence`
+    pub prefix: Vec<String>,
+    /// Matched by `alt`
+    pub sentence: Vec<String>,
+    /// The terminals which are named tokens
+    pub(crate) named: HashSet<String>,
+}
+
+impl fmt::Display for ShadowedAlt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "alternative {} of {} is shadowed by alternative {}: {} is a prefix of {}",
+               self.alt, self.rule, self.by, toks_str(&self.prefix, &self.named),
+               toks_str(&self.sentence, &self.named))
+    }
+}
+
+/// The sentences of up to `max_len` terminals in the CFG but not the PEG,
+/// and the alternatives shadowed on strings of up to that length.
+#[derive(Debug, Clone)]
+pub struct PegAnalysis {
+    pub max_len: usize,
+    /// The number of sentences of the CFG checked
+    pub sentences: usize,
+    pub lost: Vec<Vec<String>>,
+    pub shadowed: Vec<ShadowedAlt>,
+    /// The terminals which are named tokens
+    pub(crate) named: HashSet<String>,
+}
+
+impl PegAnalysis {
+    /// Does the PEG reject any sentence of the CFG?
+    pub fn changes_language(&self) -> bool {
+        !self.lost.is_empty()
+    }
+}
+
+impl fmt::Display for PegAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.lost.first() {
+            Some(lost) => writeln!(f, "as a PEG, rejects {} of its {} sentences of up to {} terminals, such as {}",
+                                   self.lost.len(), self.sentences, self.max_len, toks_str(lost, &self.named))?,
+            None => writeln!(f, "as a PEG, accepts all {} of its sentences of up to {} terminals",
+                             self.sentences, self.max_len)?,
+        }
+        for shadowed in &self.shadowed {
+            writeln!(f, "{}", shadowed)?;
+        }
+        Ok(())
+    }
+}
+
+fn toks_str(toks: &[String], named: &HashSet<String>) -> String {
+    if toks.is_empty() {
+        return "the empty string".to_string();
+    }
+    let toks: Vec<String> = toks.iter().map(|tok| term_str(tok, named.contains(tok))).collect();
+    toks.join(" ")
+}
+
+/// Matches sentences as the PEG would.
+struct PegMatcher {
+    rules: IdxRules,
+}
+
+impl PegMatcher {
+    fn accepts(&self, sentence: &[String]) -> bool {
+        !self.rules.lhss.is_empty() && self.match_rule(0, sentence, 0, &mut vec![]) == Some(sentence.len())
+    }
+
+    /// Where a match of rule `ridx` from `pos` ends, if there is one. A rule
+    /// re-entered at the same position (left recursion, on which a PEG
+    /// would loop) fails.
+    fn match_rule(&self, ridx: usize, toks: &[String], pos: usize, active: &mut Vec<(usize, usize)>)
+                  -> Option<usize> {
+        if active.contains(&(ridx, pos)) {
+            return None;
+        }
+        active.push((ridx, pos));
+        let end = self.rules.alts[ridx].iter().find_map(|alt| self.match_seq(alt, toks, pos, active));
+        active.pop();
+        end
+    }
+
+    fn match_seq(&self, syms: &[Sym], toks: &[String], pos: usize, active: &mut Vec<(usize, usize)>)
+                 -> Option<usize> {
+        let mut pos = pos;
+        for sym in syms {
+            pos = match sym {
+                Sym::Term(tok) if toks.get(pos) == Some(tok) => pos + 1,
+                Sym::Term(_) | Sym::NonTerm(None) => return None,
+                Sym::NonTerm(Some(ridx)) => self.match_rule(*ridx, toks, pos, active)?,
+            };
+        }
+        Some(pos)
+    }
+}
+
+impl Cfg {
+    /// Compare the CFG with it read as a PEG, on sentences of up to
+    /// `max_len` terminals. This enumerates the sentences of each
+    /// alternative, so is only practical for small lengths.
+    pub fn peg_analysis(&self, max_len: usize) -> PegAnalysis {
+        let matcher = PegMatcher { rules: IdxRules::new(self) };
+        let sentences = self.enumerate(max_len);
+        let lost = sentences.sentences()
+            .iter()
+            .filter(|sentence| !matcher.accepts(sentence))
+            .cloned()
+            .collect();
+
+        let alt_name = FreshNames::new(self).fresh("alt");
+        let mut shadowed = Vec::<ShadowedAlt>::new();
+        for rule in &self.rules {
+            let alt_sentences: Vec<Vec<Vec<String>>> = rule.rhs
+                .iter()
+                .map(|alt| {
+                    let mut rules = vec![CfgRule::new(alt_name.clone(), vec![alt.clone()])];
+                    rules.extend(self.rules.iter().cloned());
+                    Cfg::new(rules).enumerate(max_len).sentences().to_vec()
+                })
+                .collect();
+            for (aidx, sentences) in alt_sentences.iter().enumerate() {
+                let shadow = sentences.iter().find_map(|sentence| {
+                    alt_sentences[..aidx].iter().enumerate().find_map(|(by, prefixes)| {
+                        prefixes
+                            .iter()
+                            .find(|prefix| prefix.len() < sentence.len() && sentence.starts_with(prefix))
+                            .map(|prefix| (by, prefix.clone(), sentence.clone()))
+                    })
+                });
+                if let Some((by, prefix, sentence)) = shadow {
+                    shadowed.push(ShadowedAlt {
+                        rule: rule.lhs.to_string(),
+                        alt: aidx,
+                        by,
+                        prefix,
+                        sentence,
+                        named: matcher.rules.named.clone(),
+                    });
+                }
+            }
+        }
+
+        PegAnalysis {
+            max_len,
+            sentences: sentences.sentences().len(),
+            lost,
+            shadowed,
+            named: matcher.rules.named,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammars::test_utils::toks;
+
+    use super::*;
+
+    #[test]
+    fn test_peg_analysis() {
+        // 'a' shadows 'a' 'b', so "ab" and "abc" are lost
+        let cfg = Cfg::from_yacc("%%\nS: A 'c' | A;\nA: 'a' | 'a' 'b';\n").unwrap();
+        let analysis = cfg.peg_analysis(4);
+        assert!(analysis.changes_language());
+        assert_eq!(analysis.sentences, 4);
+        assert_eq!(analysis.lost, vec![toks("ab"), toks("abc")]);
+        assert_eq!(analysis.shadowed, vec![
+            ShadowedAlt {
+                rule: "A".to_string(),
+                alt: 1,
+                by: 0,
+                prefix: toks("a"),
+                sentence: toks("ab"),
+                named: HashSet::new(),
+            },
+        ]);
+        assert_eq!(analysis.shadowed.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+                   vec!["alternative 1 of A is shadowed by alternative 0: 'a' is a prefix of 'a' 'b'"]);
+
+        // the longer alternative first loses nothing
+        let cfg = Cfg::from_yacc("%%\nS: A 'c' | A;\nA: 'a' 'b' | 'a';\n").unwrap();
+        let analysis = cfg.peg_analysis(4);
+        assert!(!analysis.changes_language());
+        assert!(analysis.shadowed.is_empty());
+        assert_eq!(analysis.to_string(), "as a PEG, accepts all 4 of its sentences of up to 4 terminals\n");
+
+        // named tokens are shown bare
+        let cfg = Cfg::from_yacc("%token ID\n%%\nS: ID | ID '(' ')';\n").unwrap();
+        assert_eq!(cfg.peg_analysis(4).to_string(),
+                   "as a PEG, rejects 1 of its 2 sentences of up to 4 terminals, such as ID '(' ')'\n\
+                    alternative 1 of S is shadowed by alternative 0: ID is a prefix of ID '(' ')'\n");
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::grammars::stats::{CfgGenStats, CfgReject};
+
+/// A snapshot of a generation run, handed to the progress callback after
+/// every batch of CFGs. Counts are cumulative for the run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CfgGenProgress {
+    /// Grammar size
+    pub cfg_size: usize,
+    /// CFGs generated so far
+    pub attempted: usize,
+    /// CFGs which will be generated in total, if known up front
+    pub total: Option<usize>,
+    /// CFGs rejected as some of their non-terminals are unreachable
+    pub unreachable: usize,
+    /// CFGs rejected as some of their non-terminals are unproductive
+    pub unproductive: usize,
+    /// CFGs handed to the LR tools
+    pub productive: usize,
+    /// CFGs rejected by lrpar
+    pub lrpar_rejected: usize,
+    /// CFGs rejected by Bison
+    pub bison_rejected: usize,
+    /// CFGs rejected by Hyacc
+    pub hyacc_rejected: usize,
+    /// CFGs on which Hyacc timed out
+    pub hyacc_timeouts: usize,
+    /// CFGs accepted as LR(1)
+    pub lr1: usize,
+    /// CFGs accepted as LR(k) (but not LR(1))
+    pub lrk: usize,
+    /// Time spent generating so far
+    pub elapsed: Duration,
+    /// Estimated time until the run finishes, if it can be estimated yet
+    pub eta: Option<Duration>,
+}
+
+impl CfgGenProgress {
+    pub(crate) fn new(
+        cfg_size: usize,
+        stats: &CfgGenStats,
+        total: Option<usize>,
+        elapsed: Duration,
+        eta: Option<Duration>,
+    ) -> Self {
+        Self {
+            cfg_size,
+            attempted: stats.attempted,
+            total,
+            unreachable: stats.rejected(CfgReject::Unreachable),
+            unproductive: stats.rejected(CfgReject::Unproductive),
+            productive: stats.productive(),
+            lrpar_rejected: stats.rejected(CfgReject::Lrpar),
+            bison_rejected: stats.rejected(CfgReject::Bison),
+            hyacc_rejected: stats.rejected(CfgReject::Hyacc),
+            hyacc_timeouts: stats.rejected(CfgReject::HyaccTimeout),
+            lr1: stats.lr1,
+            lrk: stats.lrk,
+            elapsed,
+            eta,
+        }
+    }
+}
+
+impl fmt::Display for CfgGenProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "size {}: attempted {}", self.cfg_size, self.attempted)?;
+        if let Some(total) = self.total {
+            write!(f, "/{}", total)?;
+        }
+        write!(f, ", unreachable {}, unproductive {}, lr(1) {}, lr(k) {}, elapsed {}s",
+               self.unreachable,
+               self.unproductive,
+               self.lr1,
+               self.lrk,
+               self.elapsed.as_secs())?;
+        match self.eta {
+            Some(eta) => write!(f, ", eta {}s", eta.as_secs()),
+            None => write!(f, ", eta unknown"),
+        }
+    }
+}
+
+/// The default progress callback: logs a line per batch.
+pub fn log_progress(progress: &CfgGenProgress) {
+    info!("{}", progress);
+}
+
+/// Estimate how long `remaining` more attempts will take, going by the
+/// average time per attempt so far.
+pub(crate) fn eta(elapsed: Duration, attempted: usize, remaining: usize) -> Option<Duration> {
+    if attempted == 0 {
+        return None;
+    }
+    Some(elapsed.mul_f64(remaining as f64 / attempted as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eta() {
+        assert_eq!(eta(Duration::from_secs(10), 0, 100), None);
+        assert_eq!(eta(Duration::from_secs(10), 20, 60), Some(Duration::from_secs(30)));
+        assert_eq!(eta(Duration::from_secs(10), 20, 0), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut stats = CfgGenStats::default();
+        for _ in 0..3 {
+            stats.record_cfg(&[]);
+        }
+        stats.record_reject(CfgReject::Unreachable);
+        stats.record_reject(CfgReject::Unproductive);
+        let progress = CfgGenProgress::new(
+            10, &stats, Some(6), Duration::from_secs(3), Some(Duration::from_secs(3)));
+
+        assert_eq!(progress.productive, 1);
+        assert_eq!(progress.to_string(),
+                   "size 10: attempted 3/6, unreachable 1, unproductive 1, lr(1) 0, lr(k) 0, \
+                    elapsed 3s, eta 3s");
+    }
+}
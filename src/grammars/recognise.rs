@@ -0,0 +1,49 @@
+//! Deciding whether a sentence is in the language of a CFG.
+
+use crate::grammars::Cfg;
+use crate::grammars::earley::EarleyParser;
+
+/// Recognises the sentences of a CFG.
+pub struct Recogniser {
+    parser: EarleyParser,
+}
+
+impl Recogniser {
+    fn new(cfg: &Cfg) -> Self {
+        Self {
+            parser: cfg.earley(),
+        }
+    }
+
+    /// Is `sentence` in the language?
+    pub fn accepts(&self, sentence: &[String]) -> bool {
+        self.parser.recognise(sentence)
+    }
+}
+
+impl Cfg {
+    pub fn recogniser(&self) -> Recogniser {
+        Recogniser::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammars::test_utils::toks;
+
+    use super::*;
+
+    #[test]
+    fn test_accepts() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S 'b' | A;\nA: 'c' A | ;\n").unwrap();
+        let rec = cfg.recogniser();
+        for s in &["", "c", "ab", "acccb", "aacbb"] {
+            assert!(rec.accepts(&toks(s)), "{}", s);
+        }
+        for s in &["a", "ba", "abc", "aabbb", "d"] {
+            assert!(!rec.accepts(&toks(s)), "{}", s);
+        }
+        // every enumerated sentence is accepted
+        assert!(cfg.enumerate(6).sentences().iter().all(|s| rec.accepts(s)));
+    }
+}
@@ -0,0 +1,142 @@
+//! Reduction of a CFG: removing the non-terminals which derive no sentence
+//! (unproductive) and then those no longer reachable from the start rule.
+//! The order matters: removing unproductive rules can make others unreachable.
+
+use std::collections::HashSet;
+
+use crate::grammars::{Cfg, CfgRule, LexSymbol, RuleAlt};
+use crate::grammars::transform::{productive, reachable};
+
+/// A reduced CFG, along with what was removed from the original to get it.
+#[derive(Debug, Clone)]
+pub struct ReducedCfg {
+    cfg: Cfg,
+    unproductive: Vec<String>,
+    unreachable: Vec<String>,
+    removed_alts: usize,
+}
+
+impl ReducedCfg {
+    pub fn cfg(&self) -> &Cfg {
+        &self.cfg
+    }
+
+    /// The non-terminals removed for deriving no sentence, in rule order.
+    /// If the start symbol is among them, the language is empty and so is
+    /// the reduced CFG.
+    pub fn unproductive(&self) -> &[String] {
+        &self.unproductive
+    }
+
+    /// The (productive) non-terminals removed for being unreachable from the
+    /// start rule, in rule order
+    pub fn unreachable(&self) -> &[String] {
+        &self.unreachable
+    }
+
+    /// How many alternatives of the remaining rules were removed for using
+    /// an unproductive non-terminal
+    pub fn removed_alts(&self) -> usize {
+        self.removed_alts
+    }
+
+    /// Was anything removed?
+    pub fn is_changed(&self) -> bool {
+        !self.unproductive.is_empty() || !self.unreachable.is_empty() || self.removed_alts > 0
+    }
+}
+
+fn as_slices(rules: &[CfgRule]) -> Vec<(&str, Vec<&[LexSymbol]>)> {
+    rules
+        .iter()
+        .map(|rule| (rule.lhs.as_str(), rule.rhs.iter().map(|alt| alt.lex_symbols.as_slice()).collect()))
+        .collect()
+}
+
+impl Cfg {
+    /// Remove the unproductive non-terminals (and the alternatives using
+    /// them), then the unreachable ones. The language is unchanged.
+    pub fn reduce(&self) -> ReducedCfg {
+        let productive = productive(&as_slices(&self.rules));
+        let unproductive: Vec<String> = self.rules
+            .iter()
+            .filter(|rule| !productive.contains(&rule.lhs))
+            .map(|rule| rule.lhs.to_string())
+            .collect();
+        let start_productive = self.start_rule().is_some_and(|rule| productive.contains(&rule.lhs));
+
+        let mut removed_alts = 0;
+        let mut rules = Vec::<CfgRule>::new();
+        if start_productive {
+            for rule in self.rules.iter().filter(|rule| productive.contains(&rule.lhs)) {
+                let alts: Vec<RuleAlt> = rule.rhs
+                    .iter()
+                    .filter(|alt| alt.lex_symbols.iter().all(|sym| match sym {
+                        LexSymbol::NonTerm(nt) => productive.contains(&nt.tok),
+                        LexSymbol::Term(_) => true,
+                    }))
+                    .cloned()
+                    .collect();
+                removed_alts += rule.rhs.len() - alts.len();
+                rules.push(CfgRule::new(rule.lhs.to_string(), alts));
+            }
+        }
+
+        let reachable: HashSet<String> = reachable(&as_slices(&rules));
+        let unreachable: Vec<String> = self.rules
+            .iter()
+            .filter(|rule| productive.contains(&rule.lhs) && !reachable.contains(&rule.lhs))
+            .map(|rule| rule.lhs.to_string())
+            .collect();
+        rules.retain(|rule| reachable.contains(&rule.lhs));
+
+        ReducedCfg {
+            cfg: Cfg::new(rules),
+            unproductive,
+            unreachable,
+            removed_alts,
+        }
+    }
+
+    /// Is every non-terminal productive and reachable from the start rule?
+    pub fn is_reduced(&self) -> bool {
+        !self.reduce().is_changed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reduce() {
+        // B and C only derive each other; removing them leaves D unreachable
+        let cfg = Cfg::from_yacc("%%\nS: 'a' A | 'c' | B D;\nA: 'x' | ;\nB: 'b' C;\nC: B 'c';\nD: 'd';\nE: 'e';\n")
+            .unwrap();
+        assert!(!cfg.is_reduced());
+        let reduced = cfg.reduce();
+        assert_eq!(reduced.cfg().to_string(), "S: 'a' A | 'c'\n;\nA: 'x' | \n;\n");
+        assert_eq!(reduced.unproductive(), &["B".to_string(), "C".to_string()]);
+        assert_eq!(reduced.unreachable(), &["D".to_string(), "E".to_string()]);
+        assert_eq!(reduced.removed_alts(), 1);
+        assert!(reduced.cfg().is_reduced());
+    }
+
+    #[test]
+    fn test_reduce_unproductive_first() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' | A B;\nA: A 'a';\nB: 'b';\n").unwrap();
+        let reduced = cfg.reduce();
+        assert_eq!(reduced.cfg().to_string(), "S: 'a'\n;\n");
+        assert_eq!(reduced.unproductive(), &["A".to_string()]);
+        assert_eq!(reduced.unreachable(), &["B".to_string()]);
+    }
+
+    #[test]
+    fn test_reduce_empty_language() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' S;\n").unwrap();
+        let reduced = cfg.reduce();
+        assert!(reduced.cfg().rules.is_empty());
+        assert_eq!(reduced.unproductive(), &["S".to_string()]);
+        assert!(Cfg::from_yacc("%%\nS: 'a' S | 'b';\n").unwrap().is_reduced());
+    }
+}
@@ -0,0 +1,380 @@
+//! Random sentences of a CFG, for use as parser inputs.
+//!
+//! Without a target length, a derivation is grown top-down choosing among
+//! the alternatives which can still finish within the maximum depth, judged
+//! by the height of their smallest derivation, so sampling always terminates.
+//! With a target length, the number of derivation trees of each length and
+//! bounded depth is counted first, so that only alternatives (and splits of
+//! the length between their symbols) which can yield exactly that length
+//! are chosen. Weighting choices by these counts makes every such tree
+//! equally likely: for an unambiguous CFG, every sentence.
+
+use std::collections::HashMap;
+
+use rand::{Rng, rngs::SmallRng, SeedableRng};
+
+use crate::grammars::{Cfg, CfgError};
+use crate::grammars::transform::{IdxRules, Sym};
+use crate::grammars::tree::DerivTree;
+
+/// How the choices in a derivation are weighted.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SampleWeighting {
+    /// Every (viable) alternative of a rule is equally likely
+    UniformAlts,
+    /// Every derivation tree of the target length is equally likely. This
+    /// needs a target length.
+    UniformSentences,
+}
+
+/// What sentences a `Sampler` produces.
+#[derive(Debug, Clone)]
+pub struct SampleOptions {
+    /// The number of terminals in every sentence
+    pub length: Option<usize>,
+    /// The largest number of non-terminal nodes on a path from the root of
+    /// the derivation tree. Without a target length this may be exceeded if
+    /// the CFG has no derivation shallow enough, in which case the shallowest
+    /// derivations are used.
+    pub max_depth: usize,
+    pub weighting: SampleWeighting,
+    /// Seed for reproducible samples
+    pub seed: Option<u64>,
+}
+
+impl Default for SampleOptions {
+    fn default() -> Self {
+        Self {
+            length: None,
+            max_depth: 32,
+            weighting: SampleWeighting::UniformAlts,
+            seed: None,
+        }
+    }
+}
+
+impl SampleOptions {
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn weighting(mut self, weighting: SampleWeighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// A random sentence, along with its derivation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub sentence: Vec<String>,
+    pub tree: DerivTree,
+}
+
+impl IdxRules {
+    /// For each rule, the height of its shallowest derivation tree, or
+    /// `None` if it derives no sentence.
+    fn heights(&self) -> Vec<Option<usize>> {
+        let mut heights = vec![None; self.lhss.len()];
+        loop {
+            let mut changed = false;
+            for ridx in 0..self.lhss.len() {
+                let height = self.alts[ridx].iter().filter_map(|alt| self.alt_height(alt, &heights)).min();
+                if height.is_some() && (heights[ridx].is_none() || height < heights[ridx]) {
+                    heights[ridx] = height;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return heights;
+            }
+        }
+    }
+
+    fn alt_height(&self, alt: &[Sym], heights: &[Option<usize>]) -> Option<usize> {
+        let mut height = 1;
+        for sym in alt {
+            if let Sym::NonTerm(ridx) = sym {
+                height = height.max(1 + (*ridx).and_then(|ridx| heights[ridx])?);
+            }
+        }
+        Some(height)
+    }
+}
+
+/// Counts of derivation trees, by sentence length and maximum depth. Counts
+/// grow exponentially, so are kept as floats: only their ratios matter.
+struct Counts {
+    rules: IdxRules,
+    rule_counts: HashMap<(usize, usize, usize), f64>,
+    suffix_counts: HashMap<(usize, usize, usize, usize, usize), f64>,
+}
+
+impl Counts {
+    /// The number of trees for rule `ridx` of `len` terminals and at most
+    /// `depth` deep.
+    fn rule(&mut self, ridx: usize, len: usize, depth: usize) -> f64 {
+        if depth == 0 {
+            return 0.0;
+        }
+        if let Some(count) = self.rule_counts.get(&(ridx, len, depth)) {
+            return *count;
+        }
+        let count = (0..self.rules.alts[ridx].len())
+            .map(|aidx| self.suffix(ridx, aidx, 0, len, depth - 1))
+            .sum();
+        self.rule_counts.insert((ridx, len, depth), count);
+        count
+    }
+
+    /// The number of ways the symbols of an alternative from `sidx` on can
+    /// derive `len` terminals in trees at most `depth` deep.
+    fn suffix(&mut self, ridx: usize, aidx: usize, sidx: usize, len: usize, depth: usize) -> f64 {
+        let key = (ridx, aidx, sidx, len, depth);
+        if let Some(count) = self.suffix_counts.get(&key) {
+            return *count;
+        }
+        let count = match self.rules.alts[ridx][aidx].get(sidx).cloned() {
+            None => if len == 0 { 1.0 } else { 0.0 },
+            Some(Sym::Term(_)) => if len == 0 { 0.0 } else { self.suffix(ridx, aidx, sidx + 1, len - 1, depth) },
+            Some(Sym::NonTerm(None)) => 0.0,
+            Some(Sym::NonTerm(Some(nt))) => (0..=len)
+                .map(|n| self.split(ridx, aidx, sidx, nt, n, len, depth))
+                .sum(),
+        };
+        self.suffix_counts.insert(key, count);
+        count
+    }
+
+    /// As `suffix`, when non-terminal `nt` at `sidx` derives `n` of the terminals.
+    #[allow(clippy::too_many_arguments)]
+    fn split(&mut self, ridx: usize, aidx: usize, sidx: usize, nt: usize, n: usize, len: usize, depth: usize) -> f64 {
+        let nt_count = self.rule(nt, n, depth);
+        if nt_count == 0.0 {
+            return 0.0;
+        }
+        nt_count * self.suffix(ridx, aidx, sidx + 1, len - n, depth)
+    }
+}
+
+/// Produces random sentences of a CFG.
+pub struct Sampler {
+    counts: Counts,
+    heights: Vec<Option<usize>>,
+    opts: SampleOptions,
+    rng: SmallRng,
+}
+
+impl Sampler {
+    fn new(cfg: &Cfg, opts: &SampleOptions) -> Result<Self, CfgError> {
+        if opts.length.is_none() && opts.weighting == SampleWeighting::UniformSentences {
+            return Err(CfgError::new("Uniform sampling of sentences needs a target length".to_string()));
+        }
+        let rules = IdxRules::new(cfg);
+        let heights = rules.heights();
+        let rng = match opts.seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        };
+
+        Ok(Self {
+            counts: Counts { rules, rule_counts: HashMap::new(), suffix_counts: HashMap::new() },
+            heights,
+            opts: opts.clone(),
+            rng,
+        })
+    }
+
+    /// A random sentence, or `None` if the CFG has none of the target
+    /// length within the maximum depth (or none at all).
+    pub fn sample(&mut self) -> Option<Sample> {
+        if self.counts.rules.lhss.is_empty() {
+            return None;
+        }
+        let tree = match self.opts.length {
+            Some(len) => {
+                if self.counts.rule(0, len, self.opts.max_depth) == 0.0 {
+                    return None;
+                }
+                self.derive_len(0, len, self.opts.max_depth)
+            }
+            None => {
+                self.heights[0]?;
+                self.derive(0, self.opts.max_depth)
+            }
+        };
+
+        Some(Sample { sentence: tree.sentence(), tree })
+    }
+
+    /// Pick an index with probability proportional to its weight, or
+    /// uniformly among those of non-zero weight.
+    fn choose(&mut self, weights: &[f64], proportional: bool) -> usize {
+        let weights: Vec<f64> = weights
+            .iter()
+            .map(|w| if proportional || *w == 0.0 { *w } else { 1.0 })
+            .collect();
+        let mut r = self.rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (i, w) in weights.iter().enumerate() {
+            if *w > 0.0 {
+                if r < *w {
+                    return i;
+                }
+                r -= w;
+            }
+        }
+        weights.iter().rposition(|w| *w > 0.0).expect("some choice has a non-zero weight")
+    }
+
+    /// A tree for the productive `ridx`, at most `depth` deep if there is one.
+    fn derive(&mut self, ridx: usize, depth: usize) -> DerivTree {
+        let alt_heights: Vec<Option<usize>> = self.counts.rules.alts[ridx]
+            .iter()
+            .map(|alt| self.counts.rules.alt_height(alt, &self.heights))
+            .collect();
+        let fits: Vec<f64> = alt_heights
+            .iter()
+            .map(|h| if h.is_some_and(|h| h <= depth) { 1.0 } else { 0.0 })
+            .collect();
+        let weights = if fits.iter().any(|w| *w > 0.0) {
+            fits
+        } else {
+            let min_height = alt_heights.iter().flatten().min().cloned();
+            alt_heights.iter().map(|h| if h.is_some() && *h == min_height { 1.0 } else { 0.0 }).collect()
+        };
+        let aidx = self.choose(&weights, false);
+
+        let children = self.counts.rules.alts[ridx][aidx]
+            .clone()
+            .into_iter()
+            .map(|sym| match sym {
+                Sym::Term(tok) => {
+                    let named = self.counts.rules.named.contains(&tok);
+                    DerivTree::Term { tok, named }
+                }
+                Sym::NonTerm(nt) => self.derive(nt.expect("productive alternatives use defined rules"),
+                                                depth.saturating_sub(1)),
+            })
+            .collect();
+
+        DerivTree::NonTerm { lhs: self.counts.rules.lhss[ridx].to_string(), alt: aidx, children }
+    }
+
+    /// A tree for `ridx` of `len` terminals and at most `depth` deep, where
+    /// there is known to be one.
+    fn derive_len(&mut self, ridx: usize, len: usize, depth: usize) -> DerivTree {
+        let proportional = self.opts.weighting == SampleWeighting::UniformSentences;
+        let weights: Vec<f64> = (0..self.counts.rules.alts[ridx].len())
+            .map(|aidx| self.counts.suffix(ridx, aidx, 0, len, depth - 1))
+            .collect();
+        let aidx = self.choose(&weights, proportional);
+
+        let alt = self.counts.rules.alts[ridx][aidx].clone();
+        let mut children = Vec::<DerivTree>::new();
+        let mut left = len;
+        for (sidx, sym) in alt.into_iter().enumerate() {
+            match sym {
+                Sym::Term(tok) => {
+                    let named = self.counts.rules.named.contains(&tok);
+                    children.push(DerivTree::Term { tok, named });
+                    left -= 1;
+                }
+                Sym::NonTerm(nt) => {
+                    let nt = nt.expect("alternatives with a derivation use defined rules");
+                    let weights: Vec<f64> = (0..=left)
+                        .map(|n| self.counts.split(ridx, aidx, sidx, nt, n, left, depth - 1))
+                        .collect();
+                    let n = self.choose(&weights, proportional);
+                    children.push(self.derive_len(nt, n, depth - 1));
+                    left -= n;
+                }
+            }
+        }
+
+        DerivTree::NonTerm { lhs: self.counts.rules.lhss[ridx].to_string(), alt: aidx, children }
+    }
+}
+
+impl Cfg {
+    /// A sampler of random sentences as set out in `opts`.
+    pub fn sampler(&self, opts: &SampleOptions) -> Result<Sampler, CfgError> {
+        Sampler::new(self, opts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> Cfg {
+        Cfg::from_yacc("%%\nS: 'a' S 'b' | A;\nA: 'c' A | ;\n").unwrap()
+    }
+
+    #[test]
+    fn test_sample_depth() {
+        let cfg = cfg();
+        let mut sampler = cfg.sampler(&SampleOptions::default().max_depth(4).seed(1)).unwrap();
+        for _ in 0..50 {
+            let sample = sampler.sample().unwrap();
+            assert!(sample.tree.depth() <= 4);
+            assert_eq!(sample.sentence, sample.tree.sentence());
+        }
+
+        // too shallow: the shallowest derivation is used
+        let mut sampler = cfg.sampler(&SampleOptions::default().max_depth(0).seed(1)).unwrap();
+        assert_eq!(sampler.sample().unwrap().tree.depth(), 2);
+    }
+
+    #[test]
+    fn test_sample_length() {
+        let cfg = cfg();
+        for weighting in &[SampleWeighting::UniformAlts, SampleWeighting::UniformSentences] {
+            let opts = SampleOptions::default().length(5).weighting(*weighting).seed(7);
+            let mut sampler = cfg.sampler(&opts).unwrap();
+            for _ in 0..50 {
+                let sample = sampler.sample().unwrap();
+                assert_eq!(sample.sentence.len(), 5);
+            }
+        }
+        // sentences are a^n c^m b^n, so have no 'b' without an 'a'
+        let no_sentence = Cfg::from_yacc("%%\nS: 'a' 'b' S | 'a';\n").unwrap();
+        let mut sampler = no_sentence.sampler(&SampleOptions::default().length(2)).unwrap();
+        assert_eq!(sampler.sample(), None);
+    }
+
+    #[test]
+    fn test_sample_uniform_sentences() {
+        // the 5 sentences of length 2 are equally likely, though 4 of them use `S: C C`
+        let cfg = Cfg::from_yacc("%%\nS: 'a' 'b' | C C;\nC: 'c' | 'd';\n").unwrap();
+        let opts = SampleOptions::default().length(2).weighting(SampleWeighting::UniformSentences).seed(3);
+        let mut sampler = cfg.sampler(&opts).unwrap();
+        let mut counts = HashMap::<String, usize>::new();
+        for _ in 0..4000 {
+            *counts.entry(sampler.sample().unwrap().sentence.concat()).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 5);
+        for count in counts.values() {
+            assert!(*count > 650 && *count < 950, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_sample_errors() {
+        let cfg = cfg();
+        let opts = SampleOptions::default().weighting(SampleWeighting::UniformSentences);
+        assert!(cfg.sampler(&opts).is_err());
+        let unproductive = Cfg::from_yacc("%%\nS: 'a' S;\n").unwrap();
+        assert_eq!(unproductive.sampler(&SampleOptions::default()).unwrap().sample(), None);
+    }
+}
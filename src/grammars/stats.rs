@@ -0,0 +1,265 @@
+use std::{collections::BTreeMap, fmt};
+
+use prettytable::{row, cell};
+use prettytable::Table;
+
+use crate::grammars::CfgRule;
+use crate::grammars::gen::CfgLr1Result;
+
+/// Why a generated CFG did not make it to the output directory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum CfgReject {
+    /// Some non-terminals are not reachable from the root rule
+    Unreachable,
+    /// Some non-terminals do not generate a sentence
+    Unproductive,
+    /// lrpar found conflicts
+    Lrpar,
+    /// Bison found conflicts or useless rules
+    Bison,
+    /// Hyacc could not build an LR(k) parser
+    Hyacc,
+    /// Hyacc did not finish within its time limit
+    HyaccTimeout,
+}
+
+impl fmt::Display for CfgReject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            CfgReject::Unreachable => "unreachable",
+            CfgReject::Unproductive => "unproductive",
+            CfgReject::Lrpar => "rejected by lrpar",
+            CfgReject::Bison => "rejected by bison",
+            CfgReject::Hyacc => "rejected by hyacc",
+            CfgReject::HyaccTimeout => "hyacc timeout",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+const REJECT_REASONS: [CfgReject; 6] = [
+    CfgReject::Unreachable,
+    CfgReject::Unproductive,
+    CfgReject::Lrpar,
+    CfgReject::Bison,
+    CfgReject::Hyacc,
+    CfgReject::HyaccTimeout,
+];
+
+/// Statistics collected while generating CFGs: how many made it through
+/// each stage of the pipeline, why the others were thrown away, and the
+/// shape of the generated rules.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct CfgGenStats {
+    /// CFGs generated
+    pub(crate) attempted: usize,
+    /// CFGs accepted as LR(1) by both lrpar and Bison
+    pub(crate) lr1: usize,
+    /// CFGs accepted as LR(k) by Hyacc but rejected by Bison
+    pub(crate) lrk: usize,
+    /// Number of CFGs rejected for each reason. A CFG handed to the LR tools
+    /// is counted once for every tool that rejected it.
+    rejected: BTreeMap<CfgReject, usize>,
+    /// number of alternatives in a rule => number of such rules
+    alts_hist: BTreeMap<usize, usize>,
+    /// number of symbols in an alternative => number of such alternatives
+    alt_len_hist: BTreeMap<usize, usize>,
+}
+
+impl CfgGenStats {
+    /// Record the shape of a freshly generated CFG.
+    pub(crate) fn record_cfg(&mut self, rules: &[CfgRule]) {
+        self.attempted += 1;
+        for rule in rules {
+            *self.alts_hist.entry(rule.rhs.len()).or_insert(0) += 1;
+            for alt in &rule.rhs {
+                *self.alt_len_hist.entry(alt.lex_symbols.len()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub(crate) fn record_reject(&mut self, reason: CfgReject) {
+        *self.rejected.entry(reason).or_insert(0) += 1;
+    }
+
+    /// Record the verdicts of the LR tools on a CFG.
+    pub(crate) fn record_lr_checks(&mut self, res: &CfgLr1Result) {
+        if !res.lrpar_lr1 {
+            self.record_reject(CfgReject::Lrpar);
+        }
+        if !res.bison_lr1 {
+            self.record_reject(CfgReject::Bison);
+        }
+        if res.hyacc_timeout {
+            self.record_reject(CfgReject::HyaccTimeout);
+        } else if !res.hyacc_lr1 {
+            self.record_reject(CfgReject::Hyacc);
+        }
+
+        if res.is_lr1() {
+            self.lr1 += 1;
+        } else if res.is_lrk() {
+            self.lrk += 1;
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.attempted += other.attempted;
+        self.lr1 += other.lr1;
+        self.lrk += other.lrk;
+        for (reason, n) in &other.rejected {
+            *self.rejected.entry(*reason).or_insert(0) += n;
+        }
+        for (alts, n) in &other.alts_hist {
+            *self.alts_hist.entry(*alts).or_insert(0) += n;
+        }
+        for (len, n) in &other.alt_len_hist {
+            *self.alt_len_hist.entry(*len).or_insert(0) += n;
+        }
+    }
+
+    pub(crate) fn rejected(&self, reason: CfgReject) -> usize {
+        *self.rejected.get(&reason).unwrap_or(&0)
+    }
+
+    /// CFGs whose non-terminals are all reachable from the root rule
+    pub(crate) fn reachable(&self) -> usize {
+        self.attempted - self.rejected(CfgReject::Unreachable)
+    }
+
+    /// Reachable CFGs which are also productive (and were handed to the LR tools)
+    pub(crate) fn productive(&self) -> usize {
+        self.reachable() - self.rejected(CfgReject::Unproductive)
+    }
+
+    fn stages_table(&self) -> Table {
+        let stages = vec![
+            ("reachable", self.attempted, self.reachable()),
+            ("productive", self.reachable(), self.productive()),
+            ("lr(1)", self.productive(), self.lr1),
+            ("lr(k)", self.productive(), self.lrk),
+            ("overall lr(1)", self.attempted, self.lr1),
+            ("overall lr(k)", self.attempted, self.lrk),
+        ];
+        let mut table = Table::new();
+        table.add_row(row!["stage", "in", "accepted", "rate"]);
+        for (stage, total, accepted) in stages {
+            table.add_row(row![stage, total, accepted, percentage(accepted, total)]);
+        }
+
+        table
+    }
+
+    fn rejections_table(&self) -> Table {
+        let mut table = Table::new();
+        table.add_row(row!["reason", "cfgs", "of attempted"]);
+        for reason in REJECT_REASONS.iter() {
+            let n = self.rejected(*reason);
+            table.add_row(row![reason, n, percentage(n, self.attempted)]);
+        }
+
+        table
+    }
+
+    fn histogram_table(header: &str, hist: &BTreeMap<usize, usize>) -> Table {
+        let total: usize = hist.values().sum();
+        let mut table = Table::new();
+        table.add_row(row![header, "count", "share"]);
+        for (k, n) in hist {
+            table.add_row(row![k, n, percentage(*n, total)]);
+        }
+
+        table
+    }
+}
+
+impl fmt::Display for CfgGenStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "--- acceptance rate per stage ---")?;
+        write!(f, "{}", self.stages_table())?;
+        writeln!(f, "--- rejections ---")?;
+        write!(f, "{}", self.rejections_table())?;
+        writeln!(f, "--- alternatives per rule ---")?;
+        write!(f, "{}", Self::histogram_table("alts", &self.alts_hist))?;
+        writeln!(f, "--- symbols per alternative ---")?;
+        write!(f, "{}", Self::histogram_table("symbols", &self.alt_len_hist))
+    }
+}
+
+fn percentage(n: usize, total: usize) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.2}%", (n as f64) * 100.0 / (total as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammars::{Cfg, CfgRule, LexSymbol, RuleAlt, TermSymbol};
+    use crate::grammars::gen::CfgLr1Result;
+
+    use super::*;
+
+    fn lr_result(lrpar_lr1: bool, bison_lr1: bool, hyacc_lr1: bool, hyacc_timeout: bool) -> CfgLr1Result {
+        CfgLr1Result::new(Cfg::new(vec![]), "x.bison.y".to_string(), "x.hyacc.y".to_string(),
+                          lrpar_lr1, bison_lr1, hyacc_lr1, hyacc_timeout)
+    }
+
+    fn rule(lhs: &str, alt_lens: &[usize]) -> CfgRule {
+        let rhs = alt_lens
+            .iter()
+            .map(|n| {
+                let syms = (0..*n)
+                    .map(|_| LexSymbol::Term(TermSymbol::new("a".to_string())))
+                    .collect();
+                RuleAlt::new(syms)
+            })
+            .collect();
+        CfgRule::new(lhs.to_string(), rhs)
+    }
+
+    #[test]
+    fn test_stage_counts() {
+        let mut stats = CfgGenStats::default();
+        for _ in 0..5 {
+            stats.record_cfg(&[]);
+        }
+        stats.record_reject(CfgReject::Unreachable);
+        stats.record_reject(CfgReject::Unproductive);
+        stats.record_lr_checks(&lr_result(true, true, true, false));
+        stats.record_lr_checks(&lr_result(false, false, true, false));
+        stats.record_lr_checks(&lr_result(false, false, false, true));
+
+        assert_eq!(stats.attempted, 5);
+        assert_eq!(stats.reachable(), 4);
+        assert_eq!(stats.productive(), 3);
+        assert_eq!(stats.lr1, 1);
+        assert_eq!(stats.lrk, 1);
+        assert_eq!(stats.rejected(CfgReject::Lrpar), 2);
+        assert_eq!(stats.rejected(CfgReject::Bison), 2);
+        assert_eq!(stats.rejected(CfgReject::Hyacc), 0);
+        assert_eq!(stats.rejected(CfgReject::HyaccTimeout), 1);
+    }
+
+    #[test]
+    fn test_histograms() {
+        let mut stats = CfgGenStats::default();
+        stats.record_cfg(&[rule("root", &[2, 1]), rule("A", &[0])]);
+        let mut other = CfgGenStats::default();
+        other.record_cfg(&[rule("root", &[1])]);
+        stats.merge(&other);
+
+        assert_eq!(stats.attempted, 2);
+        assert_eq!(stats.alts_hist.get(&1), Some(&2));
+        assert_eq!(stats.alts_hist.get(&2), Some(&1));
+        assert_eq!(stats.alt_len_hist.get(&0), Some(&1));
+        assert_eq!(stats.alt_len_hist.get(&1), Some(&2));
+        assert_eq!(stats.alt_len_hist.get(&2), Some(&1));
+    }
+
+    #[test]
+    fn test_percentage() {
+        assert_eq!(percentage(1, 4), "25.00%");
+        assert_eq!(percentage(0, 0), "-");
+    }
+}
@@ -0,0 +1,6 @@
+//! Helpers shared by the tests.
+
+/// A sentence of single-character terminals, one for each character of `s`.
+pub(crate) fn toks(s: &str) -> Vec<String> {
+    s.chars().map(|c| c.to_string()).collect()
+}
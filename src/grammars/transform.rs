@@ -0,0 +1,238 @@
+//! Helpers shared by the grammar transformations.
+
+use std::collections::HashSet;
+
+use crate::grammars::{Cfg, CfgRule, LexSymbol, RuleAlt};
+
+/// A rule as its lhs and the symbols of each of its alternatives, which is
+/// easier to rewrite than a `CfgRule`.
+pub(crate) type SymRule = (String, Vec<Vec<LexSymbol>>);
+
+pub(crate) fn sym_rules(cfg: &Cfg) -> Vec<SymRule> {
+    cfg.rules
+        .iter()
+        .map(|rule| (rule.lhs.to_string(),
+                     rule.rhs.iter().map(|alt| alt.lex_symbols.clone()).collect()))
+        .collect()
+}
+
+/// Build a CFG out of `rules`, leaving out those not reachable from the first.
+pub(crate) fn reachable_cfg(rules: Vec<SymRule>) -> Cfg {
+    let reachable = {
+        let rules: Vec<(&str, Vec<&[LexSymbol]>)> = rules
+            .iter()
+            .map(|(lhs, alts)| (lhs.as_str(), alts.iter().map(|alt| alt.as_slice()).collect()))
+            .collect();
+        reachable(&rules)
+    };
+    let cfg_rules = rules
+        .into_iter()
+        .filter(|(lhs, _)| reachable.contains(lhs))
+        .map(|(lhs, alts)| CfgRule::new(lhs, alts.into_iter().map(RuleAlt::new).collect()))
+        .collect();
+
+    Cfg::new(cfg_rules)
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Sym {
+    Term(String),
+    /// Index of the rule, if the non-terminal has one
+    NonTerm(Option<usize>),
+}
+
+/// The rules of a CFG with non-terminals resolved to rule indices.
+#[derive(Debug, Clone)]
+pub(crate) struct IdxRules {
+    pub(crate) lhss: Vec<String>,
+    pub(crate) alts: Vec<Vec<Vec<Sym>>>,
+    /// The terminals which are named tokens
+    pub(crate) named: HashSet<String>,
+}
+
+impl IdxRules {
+    pub(crate) fn new(cfg: &Cfg) -> Self {
+        let lhss: Vec<String> = cfg.rules.iter().map(|rule| rule.lhs.to_string()).collect();
+        let alts = cfg.rules
+            .iter()
+            .map(|rule| rule.rhs
+                .iter()
+                .map(|alt| alt.lex_symbols
+                    .iter()
+                    .map(|sym| match sym {
+                        LexSymbol::Term(t) => Sym::Term(t.tok.to_string()),
+                        LexSymbol::NonTerm(nt) => Sym::NonTerm(lhss.iter().position(|lhs| *lhs == nt.tok)),
+                    })
+                    .collect())
+                .collect())
+            .collect();
+
+        Self { lhss, alts, named: cfg.named_tokens().into_iter().collect() }
+    }
+}
+
+/// Hands out non-terminal names which do not collide with any name in use:
+/// those of the rules, and of the named tokens.
+pub(crate) struct FreshNames {
+    used: HashSet<String>,
+}
+
+impl FreshNames {
+    pub(crate) fn new(cfg: &Cfg) -> Self {
+        Self {
+            used: cfg.rules
+                .iter()
+                .map(|rule| rule.lhs.to_string())
+                .chain(cfg.named_tokens())
+                .collect(),
+        }
+    }
+
+    /// `base` if it is unused, otherwise `base_1`, `base_2`, ...
+    pub(crate) fn fresh(&mut self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut i = 0;
+        while self.used.contains(&name) {
+            i += 1;
+            name = format!("{}_{}", base, i);
+        }
+        self.used.insert(name.clone());
+
+        name
+    }
+}
+
+/// The non-terminals deriving the empty string, given each rule as
+/// its lhs and the symbols of its alternatives.
+pub(crate) fn nullable(rules: &[(&str, Vec<&[LexSymbol]>)]) -> HashSet<String> {
+    let mut nullable = HashSet::<String>::new();
+    loop {
+        let mut found_nullable = false;
+        for (lhs, alts) in rules {
+            if nullable.contains(*lhs) {
+                continue;
+            }
+            let is_nullable = alts
+                .iter()
+                .any(|syms| syms.iter().all(|sym| match sym {
+                    LexSymbol::NonTerm(nt) => nullable.contains(&nt.tok),
+                    LexSymbol::Term(_) => false,
+                }));
+            if is_nullable {
+                nullable.insert((*lhs).to_string());
+                found_nullable = true;
+            }
+        }
+        if !found_nullable {
+            return nullable;
+        }
+    }
+}
+
+/// The non-terminals deriving some sentence, given each rule as its lhs and
+/// the symbols of its alternatives.
+pub(crate) fn productive(rules: &[(&str, Vec<&[LexSymbol]>)]) -> HashSet<String> {
+    let mut productive = HashSet::<String>::new();
+    loop {
+        let mut found_productive = false;
+        for (lhs, alts) in rules {
+            if productive.contains(*lhs) {
+                continue;
+            }
+            let is_productive = alts
+                .iter()
+                .any(|syms| syms.iter().all(|sym| match sym {
+                    LexSymbol::NonTerm(nt) => productive.contains(&nt.tok),
+                    LexSymbol::Term(_) => true,
+                }));
+            if is_productive {
+                productive.insert((*lhs).to_string());
+                found_productive = true;
+            }
+        }
+        if !found_productive {
+            return productive;
+        }
+    }
+}
+
+/// The non-terminals reachable from the first rule, given each rule as
+/// its lhs and the symbols of its alternatives.
+pub(crate) fn reachable(rules: &[(&str, Vec<&[LexSymbol]>)]) -> HashSet<String> {
+    let mut reachable = Vec::<&str>::new();
+    if let Some((start, _)) = rules.first() {
+        reachable.push(start);
+    }
+    let mut i = 0;
+    while let Some(nt) = reachable.get(i).cloned() {
+        if let Some((_, alts)) = rules.iter().find(|(lhs, _)| *lhs == nt) {
+            for sym in alts.iter().flat_map(|syms| syms.iter()) {
+                if let LexSymbol::NonTerm(sym_nt) = sym {
+                    if !reachable.contains(&sym_nt.tok.as_str()) {
+                        reachable.push(&sym_nt.tok);
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+
+    reachable.into_iter().map(|nt| nt.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fresh_names() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a';\nA: 'b' | S_1;\nS_1: 'c';\n").unwrap();
+        let mut names = FreshNames::new(&cfg);
+        assert_eq!(names.fresh("B"), "B");
+        assert_eq!(names.fresh("B"), "B_1");
+        assert_eq!(names.fresh("S"), "S_2");
+
+        // named tokens are taken too
+        let cfg = Cfg::from_yacc("%token T_x\n%%\nS: T_x 'x' S | 'y';\n").unwrap();
+        assert_eq!(FreshNames::new(&cfg).fresh("T_x"), "T_x_1");
+    }
+
+    #[test]
+    fn test_nullable() {
+        let cfg = Cfg::from_yacc("%%\nS: A B | 'a';\nA: B B | 'b';\nB: | 'c';\nC: C 'd';\n").unwrap();
+        let rules: Vec<(&str, Vec<&[LexSymbol]>)> = cfg.rules
+            .iter()
+            .map(|rule| (rule.lhs.as_str(),
+                         rule.rhs.iter().map(|alt| alt.lex_symbols.as_slice()).collect()))
+            .collect();
+        let mut nullable: Vec<String> = nullable(&rules).into_iter().collect();
+        nullable.sort();
+        assert_eq!(nullable, vec!["A", "B", "S"]);
+    }
+
+    #[test]
+    fn test_productive() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' A | B;\nA: 'x' B | ;\nB: 'b' C;\nC: B 'c';\n").unwrap();
+        let rules: Vec<(&str, Vec<&[LexSymbol]>)> = cfg.rules
+            .iter()
+            .map(|rule| (rule.lhs.as_str(),
+                         rule.rhs.iter().map(|alt| alt.lex_symbols.as_slice()).collect()))
+            .collect();
+        let mut productive: Vec<String> = productive(&rules).into_iter().collect();
+        productive.sort();
+        assert_eq!(productive, vec!["A", "S"]);
+    }
+
+    #[test]
+    fn test_reachable() {
+        let cfg = Cfg::from_yacc("%%\nS: A 'a';\nA: B | 'b';\nB: ;\nC: S;\n").unwrap();
+        let rules: Vec<(&str, Vec<&[LexSymbol]>)> = cfg.rules
+            .iter()
+            .map(|rule| (rule.lhs.as_str(),
+                         rule.rhs.iter().map(|alt| alt.lex_symbols.as_slice()).collect()))
+            .collect();
+        let mut reachable: Vec<String> = reachable(&rules).into_iter().collect();
+        reachable.sort();
+        assert_eq!(reachable, vec!["A", "B", "S"]);
+    }
+}
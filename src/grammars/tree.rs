@@ -0,0 +1,254 @@
+//! Derivation trees over a `Cfg`, and their export to Graphviz DOT,
+//! S-expressions and JSON.
+
+use std::fmt;
+
+use crate::grammars::{Cfg, LexSymbol, term_str};
+
+/// A derivation tree: each non-terminal node records which alternative of
+/// its rule was used, and has a child for each symbol of that alternative.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DerivTree {
+    NonTerm {
+        lhs: String,
+        /// Index of the alternative within the rule for `lhs`
+        alt: usize,
+        children: Vec<DerivTree>,
+    },
+    Term {
+        tok: String,
+        /// A named token (such as `ID`), shown bare rather than quoted
+        named: bool,
+    },
+}
+
+impl DerivTree {
+    /// The terminals at the leaves, left to right.
+    pub fn sentence(&self) -> Vec<String> {
+        let mut sentence = Vec::<String>::new();
+        self.push_leaves(&mut sentence);
+        sentence
+    }
+
+    fn push_leaves(&self, sentence: &mut Vec<String>) {
+        match self {
+            DerivTree::NonTerm { children, .. } => {
+                for child in children {
+                    child.push_leaves(sentence);
+                }
+            }
+            DerivTree::Term { tok, .. } => sentence.push(tok.to_string()),
+        }
+    }
+
+    /// The terminals at the leaves as the yacc output writes them:
+    /// `'a' ID 'b'`.
+    pub(crate) fn sentence_str(&self) -> String {
+        match self {
+            DerivTree::NonTerm { children, .. } => {
+                let leaves: Vec<String> = children
+                    .iter()
+                    .map(|c| c.sentence_str())
+                    .filter(|leaves| !leaves.is_empty())
+                    .collect();
+                leaves.join(" ")
+            }
+            DerivTree::Term { tok, named } => term_str(tok, *named),
+        }
+    }
+
+    /// The number of non-terminal nodes on the longest path from the root.
+    pub fn depth(&self) -> usize {
+        match self {
+            DerivTree::NonTerm { children, .. } => 1 + children.iter().map(|c| c.depth()).max().unwrap_or(0),
+            DerivTree::Term { .. } => 0,
+        }
+    }
+
+    /// Is this a derivation in `cfg`: does every node use an alternative of
+    /// its rule, with a child for each of its symbols?
+    pub fn is_derivation_of(&self, cfg: &Cfg) -> bool {
+        let (lhs, alt, children) = match self {
+            DerivTree::NonTerm { lhs, alt, children } => (lhs, alt, children),
+            DerivTree::Term { .. } => return true,
+        };
+        let rule_alt = match cfg.rules.iter().find(|rule| rule.lhs == *lhs).and_then(|rule| rule.rhs.get(*alt)) {
+            Some(rule_alt) => rule_alt,
+            None => return false,
+        };
+        rule_alt.lex_symbols.len() == children.len()
+            && rule_alt.lex_symbols.iter().zip(children).all(|(sym, child)| match (sym, child) {
+                (LexSymbol::Term(t), DerivTree::Term { tok, named }) => t.tok == *tok && t.named == *named,
+                (LexSymbol::NonTerm(nt), DerivTree::NonTerm { lhs, .. }) => nt.tok == *lhs && child.is_derivation_of(cfg),
+                _ => false,
+            })
+    }
+
+    pub fn to_sexp(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            DerivTree::NonTerm { lhs, alt, children } => {
+                let children: Vec<String> = children.iter().map(|c| c.to_json()).collect();
+                format!("{{\"lhs\": {}, \"alt\": {}, \"children\": [{}]}}",
+                        json_str(lhs), alt, children.join(", "))
+            }
+            DerivTree::Term { tok, named: false } => format!("{{\"term\": {}}}", json_str(tok)),
+            DerivTree::Term { tok, named: true } => format!("{{\"term\": {}, \"named\": true}}", json_str(tok)),
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tree {\n");
+        self.write_dot("n", &mut 0, "  ", &mut dot);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Write the nodes and edges of the tree, naming nodes `{prefix}{i}` and
+    /// returning the root's name.
+    fn write_dot(&self, prefix: &str, next: &mut usize, indent: &str, dot: &mut String) -> String {
+        let name = format!("{}{}", prefix, next);
+        *next += 1;
+        match self {
+            DerivTree::NonTerm { lhs, children, .. } => {
+                dot.push_str(&format!("{}{} [label={}];\n", indent, name, dot_str(lhs)));
+                for child in children {
+                    let child_name = child.write_dot(prefix, next, indent, dot);
+                    dot.push_str(&format!("{}{} -> {};\n", indent, name, child_name));
+                }
+            }
+            DerivTree::Term { tok, named } => {
+                dot.push_str(&format!("{}{} [label={}, shape=plaintext];\n",
+                                      indent, name, dot_str(&term_str(tok, *named))));
+            }
+        }
+        name
+    }
+}
+
+/// As an S-expression, with terminals written as in the yacc output:
+/// `(S 'a' (B ID))`.
+impl fmt::Display for DerivTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DerivTree::NonTerm { lhs, children, .. } => {
+                write!(f, "({}", lhs)?;
+                for child in children {
+                    write!(f, " {}", child)?;
+                }
+                write!(f, ")")
+            }
+            DerivTree::Term { tok, named } => write!(f, "{}", term_str(tok, *named)),
+        }
+    }
+}
+
+/// Several trees (such as all the parses of an ambiguous sentence) in one
+/// DOT graph, each in its own cluster so they are drawn side by side.
+pub fn trees_to_dot(trees: &[DerivTree]) -> String {
+    let mut dot = String::from("digraph trees {\n");
+    for (i, tree) in trees.iter().enumerate() {
+        dot.push_str(&format!("  subgraph cluster_{} {{\n    label=\"tree {}\";\n", i, i + 1));
+        tree.write_dot(&format!("t{}_", i), &mut 0, "    ", &mut dot);
+        dot.push_str("  }\n");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Several trees as S-expressions, one per line.
+pub fn trees_to_sexp(trees: &[DerivTree]) -> String {
+    trees.iter().map(|tree| format!("{}\n", tree)).collect()
+}
+
+/// Several trees as a JSON array.
+pub fn trees_to_json(trees: &[DerivTree]) -> String {
+    let trees: Vec<String> = trees.iter().map(|tree| tree.to_json()).collect();
+    format!("[{}]", trees.join(", "))
+}
+
+fn json_str(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn dot_str(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> DerivTree {
+        DerivTree::NonTerm {
+            lhs: "S".to_string(),
+            alt: 0,
+            children: vec![
+                DerivTree::Term { tok: "a".to_string(), named: false },
+                DerivTree::NonTerm { lhs: "B".to_string(), alt: 1, children: vec![] },
+                DerivTree::Term { tok: "\"".to_string(), named: false },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_is_derivation_of() {
+        let cfg = Cfg::from_yacc("%%\nS: 'a' B '\"';\nB: 'b' | ;\n").unwrap();
+        assert!(tree().is_derivation_of(&cfg));
+        let cfg = Cfg::from_yacc("%%\nS: 'a' B '\"';\nB: | 'b';\n").unwrap();
+        assert!(!tree().is_derivation_of(&cfg));
+    }
+
+    #[test]
+    fn test_exports() {
+        let tree = tree();
+        assert_eq!(tree.to_sexp(), "(S 'a' (B) '\"')");
+        assert_eq!(tree.to_json(),
+                   "{\"lhs\": \"S\", \"alt\": 0, \"children\": [{\"term\": \"a\"}, \
+                    {\"lhs\": \"B\", \"alt\": 1, \"children\": []}, {\"term\": \"\\\"\"}]}");
+        assert_eq!(tree.to_dot(),
+                   "digraph tree {\n  n0 [label=\"S\"];\n  n1 [label=\"'a'\", shape=plaintext];\n  n0 -> n1;\n  \
+                    n2 [label=\"B\"];\n  n0 -> n2;\n  n3 [label=\"'\\\"'\", shape=plaintext];\n  n0 -> n3;\n}\n");
+    }
+
+    #[test]
+    fn test_named_tokens() {
+        let cfg = Cfg::from_yacc("%token ID\n%%\nS: ID '=' ID;\n").unwrap();
+        let tree = DerivTree::NonTerm {
+            lhs: "S".to_string(),
+            alt: 0,
+            children: ["ID", "=", "ID"]
+                .iter()
+                .map(|tok| DerivTree::Term { tok: tok.to_string(), named: *tok == "ID" })
+                .collect(),
+        };
+        assert!(tree.is_derivation_of(&cfg));
+        assert_eq!(tree.to_sexp(), "(S ID '=' ID)");
+        assert_eq!(tree.sentence_str(), "ID '=' ID");
+        assert!(tree.to_dot().contains("n1 [label=\"ID\", shape=plaintext];"));
+        assert!(tree.to_json().contains("{\"term\": \"ID\", \"named\": true}"));
+    }
+
+    #[test]
+    fn test_exports_side_by_side() {
+        let trees = vec![tree(), DerivTree::Term { tok: "x".to_string(), named: false }];
+        let dot = trees_to_dot(&trees);
+        assert!(dot.contains("subgraph cluster_0 {") && dot.contains("subgraph cluster_1 {"));
+        assert!(dot.contains("t0_0 [label=\"S\"];") && dot.contains("t1_0 [label=\"'x'\", shape=plaintext];"));
+        assert_eq!(trees_to_sexp(&trees), "(S 'a' (B) '\"')\n'x'\n");
+        assert!(trees_to_json(&trees).starts_with("[{\"lhs\": \"S\"") && trees_to_json(&trees).ends_with("{\"term\": \"x\"}]"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fs, process};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+
+use tempfile::{Builder, TempDir};
+
+use crate::grammars::gen::{CfgGenError, CfgGenOptions};
+
+const WORK_DIR_PREFIX: &str = "cfg_run_";
+/// exit status after Ctrl-C (128 + SIGINT)
+const SIGINT_EXIT_CODE: i32 = 130;
+
+/// Working directories of the runs in progress, removed if the user hits Ctrl-C
+static ACTIVE_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static CTRLC_HANDLER: Once = Once::new();
+
+/// A uniquely named directory holding the intermediate files (the CFGs in
+/// lrpar, Bison and Hyacc format) of a generation run. Unless the files are
+/// to be kept, the directory is removed when the run finishes or panics, or
+/// (if the run handles Ctrl-C) is interrupted with Ctrl-C.
+pub(crate) struct WorkDir {
+    /// `None` if the directory is to be kept
+    dir: Option<TempDir>,
+    path: PathBuf,
+}
+
+impl WorkDir {
+    pub(crate) fn new(opts: &CfgGenOptions) -> Result<Self, CfgGenError> {
+        let mut builder = Builder::new();
+        builder.prefix(WORK_DIR_PREFIX);
+        let dir = match &opts.work_dir {
+            Some(work_dir) => builder.tempdir_in(work_dir),
+            None => builder.tempdir(),
+        }.map_err(|e| CfgGenError::new(
+            format!("Unable to create a working directory, error:\n{}", e)
+        ))?;
+        let path = dir.path().to_path_buf();
+
+        if opts.keep_files {
+            return Ok(Self {
+                dir: None,
+                path: dir.into_path(),
+            });
+        }
+        if opts.handle_ctrlc {
+            install_ctrlc_handler();
+        }
+        active_dirs().push(path.clone());
+
+        Ok(Self {
+            dir: Some(dir),
+            path,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn path_str(&self) -> &str {
+        self.path().to_str()
+            .expect("Working directory path is not valid UTF-8")
+    }
+
+    /// Remove the directory, or report where it was left if the files are kept.
+    pub(crate) fn close(mut self) -> Result<(), CfgGenError> {
+        match self.dir.take() {
+            Some(dir) => {
+                debug!("=> cleaning up working directory: {}", self.path.display());
+                dir.close()
+                    .map_err(|e|
+                        CfgGenError::new(
+                            format!("Unable to remove working directory {}, Error:\n{}",
+                                    self.path.display(),
+                                    e
+                            )
+                        ))
+            }
+            None => {
+                info!("=> keeping working directory: {}", self.path.display());
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        active_dirs().retain(|p| p != &self.path);
+    }
+}
+
+fn active_dirs() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    // a panicking run must not stop the others from cleaning up
+    ACTIVE_DIRS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn install_ctrlc_handler() {
+    CTRLC_HANDLER.call_once(|| {
+        let res = ctrlc::set_handler(|| {
+            for dir in active_dirs().iter() {
+                if let Err(e) = fs::remove_dir_all(dir) {
+                    error!("Unable to remove working directory {}: {}", dir.display(), e);
+                }
+            }
+            process::exit(SIGINT_EXIT_CODE);
+        });
+        if let Err(e) = res {
+            warn!("Unable to install a Ctrl-C handler, working directories \
+                   will be left behind if interrupted: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work_dir_removed() {
+        let _lock = crate::grammars::log::test_lock();
+        let parent = tempfile::tempdir().unwrap();
+        let opts = CfgGenOptions::default().work_dir(parent.path());
+        let wd1 = WorkDir::new(&opts).unwrap();
+        let wd2 = WorkDir::new(&opts).unwrap();
+        assert_ne!(wd1.path(), wd2.path());
+        assert!(wd1.path().starts_with(parent.path()));
+        assert!(active_dirs().contains(&wd1.path().to_path_buf()));
+        // Ctrl-C is left to the application unless it opts in
+        assert!(!CTRLC_HANDLER.is_completed());
+
+        let p = wd1.path().to_path_buf();
+        wd1.close().unwrap();
+        assert!(!p.exists());
+        assert!(!active_dirs().contains(&p));
+
+        let p = wd2.path().to_path_buf();
+        drop(wd2);
+        assert!(!p.exists());
+    }
+
+    #[test]
+    fn test_work_dir_kept() {
+        let _lock = crate::grammars::log::test_lock();
+        let parent = tempfile::tempdir().unwrap();
+        let opts = CfgGenOptions::default()
+            .work_dir(parent.path())
+            .keep_files(true);
+        let wd = WorkDir::new(&opts).unwrap();
+        let p = wd.path().to_path_buf();
+        assert!(!active_dirs().contains(&p));
+
+        wd.close().unwrap();
+        assert!(p.exists());
+    }
+}
@@ -4,11 +4,54 @@ use std::io;
 
 pub(crate) mod grammars;
 
+pub use crate::grammars::{Cfg, CfgError, CfgSize};
+pub use crate::grammars::automaton::{ConflictKind, LrAutomaton, LrConflict, LrKind};
+pub use crate::grammars::cnf::{CnfCfg, CnfNonTerm, CnfOrigin};
+pub use crate::grammars::counterexample::{ConflictExample, Counterexample};
+pub use crate::grammars::coverage::{Coverage, TestSuite};
+pub use crate::grammars::earley::{EarleyParser, ForestNode, ForestSym, PackedNode, ParseForest};
+pub use crate::grammars::enumerate::{DerivCount, Enumeration, LengthCounts};
+pub use crate::grammars::gnf::GnfCfg;
+pub use crate::grammars::gen::{CfgGenOptions, CfgGenQuota};
+pub use crate::grammars::log::{Level, Logger, set_logger, set_max_level};
+pub use crate::grammars::negative::{Mutation, NearMiss};
+pub use crate::grammars::peg::{PegAnalysis, ShadowedAlt};
+pub use crate::grammars::progress::{CfgGenProgress, log_progress};
+pub use crate::grammars::recognise::Recogniser;
+pub use crate::grammars::reduce::ReducedCfg;
+pub use crate::grammars::sample::{Sample, SampleOptions, Sampler, SampleWeighting};
+pub use crate::grammars::tree::{DerivTree, trees_to_dot, trees_to_json, trees_to_sexp};
+
 /// Generate `n` grammars between sizes (`from_size` and `to_size`)
 /// and save it in `out_dir`.
 pub fn generate(from_size: usize, to_size: usize, n: usize, out_dir: &str) -> Result<(), CfgGenError> {
+    generate_with(from_size, to_size, n, out_dir, &CfgGenOptions::default(), &mut log_progress)
+}
+
+/// As `generate`, but with the terminals and intermediate files set out in
+/// `opts`, calling `progress` after every batch of grammars.
+pub fn generate_with(from_size: usize, to_size: usize, n: usize, out_dir: &str, opts: &CfgGenOptions,
+                     progress: &mut dyn FnMut(&CfgGenProgress)) -> Result<(), CfgGenError> {
+    for cfg_size in from_size..to_size {
+        grammars::generate(cfg_size, n, out_dir, opts, progress)?;
+    }
+
+    Ok(())
+}
+
+/// For each size between `from_size` and `to_size`, keep generating grammars
+/// until `quota` is met (or its budget runs out) and save them in `out_dir`.
+pub fn generate_quota(from_size: usize, to_size: usize, quota: &CfgGenQuota, out_dir: &str) -> Result<(), CfgGenError> {
+    generate_quota_with(from_size, to_size, quota, out_dir, &CfgGenOptions::default(), &mut log_progress)
+}
+
+/// As `generate_quota`, but with the terminals and intermediate files set out in
+/// `opts`, calling `progress` after every batch of grammars.
+pub fn generate_quota_with(from_size: usize, to_size: usize, quota: &CfgGenQuota, out_dir: &str,
+                           opts: &CfgGenOptions, progress: &mut dyn FnMut(&CfgGenProgress))
+                           -> Result<(), CfgGenError> {
     for cfg_size in from_size..to_size {
-        grammars::generate(cfg_size, n, out_dir)?;
+        grammars::generate_quota(cfg_size, quota, out_dir, opts, progress)?;
     }
 
     Ok(())
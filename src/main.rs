@@ -1,9 +1,10 @@
 use std::env;
 
-pub mod grammars;
+use cfgz::{CfgGenOptions, log_progress};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let grammar_dir = &args[1];
-    let _ = cfgz::generate(10, 15, 25, grammar_dir);
+    let opts = CfgGenOptions::default().handle_ctrlc(true);
+    let _ = cfgz::generate_with(10, 15, 25, grammar_dir, &opts, &mut log_progress);
 }